@@ -0,0 +1,12 @@
+-- The nine round trips GetPackage used to make. Id lists that diesel sends
+-- as `= ANY($1)` are inlined as subqueries here.
+\set n random(1, 200)
+SELECT * FROM packages WHERE name = 'package-' || :n;
+SELECT count(*) FROM likes WHERE package = 'package-' || :n;
+SELECT * FROM versions WHERE package = 'package-' || :n;
+SELECT * FROM dependencies WHERE version IN (SELECT id FROM versions WHERE package = 'package-' || :n);
+SELECT * FROM contents WHERE version IN (SELECT id FROM versions WHERE package = 'package-' || :n);
+SELECT u.username FROM users u INNER JOIN maintainers m ON u.id = m."user" AND m.package = 'package-' || :n;
+SELECT * FROM descriptions WHERE package = 'package-' || :n;
+SELECT * FROM version_texts WHERE version IN (SELECT id FROM versions WHERE package = 'package-' || :n);
+SELECT * FROM dependency_descriptions WHERE dependency IN (SELECT d.id FROM dependencies d INNER JOIN versions v ON v.id = d.version WHERE v.package = 'package-' || :n);
@@ -0,0 +1,33 @@
+#!/bin/sh
+# Compares the old and new GetPackage queries with pgbench. The new ones are
+# read from src/db/sql, the files `load_package` includes, so they can't drift.
+#
+#   psql "$DB" -f benches/get_package/seed.sql   # once, on an empty database
+#   benches/get_package/run.sh "$DB"
+set -e
+
+DB=${1:?usage: run.sh <database url>}
+DIR=$(dirname "$0")
+SQL="$DIR/../../src/db/sql"
+TIME=${TIME:-10}
+
+AFTER=$(mktemp)
+trap 'rm -f "$AFTER"' EXIT
+
+# the two round trips GetPackage makes now, with $1 bound to a seeded name
+{
+    echo '\set n random(1, 200)'
+    for query in package versions; do
+        sed "s/\\\$1/('package-' || :n)/g" "$SQL/$query.sql"
+        echo ';'
+    done
+} > "$AFTER"
+
+bench() {
+    echo "== $1"
+    pgbench -n -M prepared -T "$TIME" -c 1 -f "$2" "$DB" \
+        | grep -E 'transactions actually processed|latency average|tps'
+}
+
+bench before "$DIR/before.sql"
+bench after "$AFTER"
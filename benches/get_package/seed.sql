@@ -0,0 +1,44 @@
+-- 200 packages with 2 maintainers, 10 likes, 2 descriptions and 5 versions
+-- each; every version has 2 texts, 3 dependencies and 20 content nodes.
+
+INSERT INTO users (username, password, salt)
+SELECT 'user' || i, '\x00', '\x00' FROM generate_series(1, 100) AS i;
+
+INSERT INTO packages (name, website, license, authors)
+SELECT 'package-' || i, 'https://example.org/' || i, 'MIT', ARRAY['author' || i]
+FROM generate_series(1, 200) AS i;
+
+INSERT INTO maintainers ("user", package)
+SELECT u.id, p.name
+FROM packages p, users u
+WHERE u.id IN (length(p.name) % 100 + 1, length(p.name) % 100 + 2);
+
+INSERT INTO likes ("user", package)
+SELECT u.id, p.name FROM packages p, users u WHERE u.id <= 10;
+
+INSERT INTO descriptions (package, language, description)
+SELECT p.name, l, 'Description of ' || p.name
+FROM packages p, unnest(ARRAY['en', 'ru']) AS l;
+
+INSERT INTO versions (package, version)
+SELECT p.name, '1.' || v || '.0' FROM packages p, generate_series(1, 5) AS v;
+
+INSERT INTO version_texts (version, language, changes, readme)
+SELECT v.id, l, '* fixed things', '# ' || v.package || E'\n\nSome **readme**.'
+FROM versions v, unnest(ARRAY['en', 'ru']) AS l;
+
+INSERT INTO dependencies (package, version, spec, type)
+SELECT 'package-' || (v.id % 200 + 1), v.id, '^1.0',
+       (ARRAY['build-require', 'runtime-require', 'optional'])[d]
+FROM versions v, generate_series(1, 3) AS d;
+
+INSERT INTO dependency_descriptions (dependency, language, description)
+SELECT d.id, l, 'Why it is needed'
+FROM dependencies d, unnest(ARRAY['en', 'ru']) AS l
+WHERE d.type = 'optional';
+
+INSERT INTO contents (version, path, type)
+SELECT v.id, 'lib/file' || c || '.lua', 'file'
+FROM versions v, generate_series(1, 20) AS c;
+
+ANALYZE;
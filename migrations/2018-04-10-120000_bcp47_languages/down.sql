@@ -0,0 +1,26 @@
+-- Rolling back would have to drop every text in a language other than ru and
+-- en, so refuse while there are any. Delete them by hand first if losing them
+-- is intended.
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM descriptions WHERE "language" NOT IN ('ru', 'en'))
+        OR EXISTS (SELECT 1 FROM version_texts WHERE "language" NOT IN ('ru', 'en'))
+        OR EXISTS (SELECT 1 FROM dependency_descriptions WHERE "language" NOT IN ('ru', 'en'))
+    THEN
+        RAISE EXCEPTION 'texts in languages other than ru and en exist and would be lost';
+    END IF;
+END
+$$;
+
+ALTER TABLE descriptions DROP CONSTRAINT descriptions_language_values,
+                         ALTER COLUMN "language" SET DATA TYPE CHAR(2),
+                         ADD CONSTRAINT descriptions_language_values CHECK ("language" IN ('ru', 'en'));
+
+ALTER TABLE version_texts DROP CONSTRAINT version_texts_language_values,
+                          ALTER COLUMN "language" SET DATA TYPE CHAR(2),
+                          ADD CONSTRAINT version_texts_language_values CHECK ("language" IN ('ru', 'en'));
+
+ALTER TABLE dependency_descriptions DROP CONSTRAINT dependency_descriptions_language_values,
+                                    ALTER COLUMN "language" SET DATA TYPE CHAR(2),
+                                    ADD CONSTRAINT dependency_descriptions_language_values
+                                        CHECK ("language" IN ('ru', 'en'));
@@ -0,0 +1,14 @@
+ALTER TABLE descriptions DROP CONSTRAINT descriptions_language_values,
+                         ALTER COLUMN "language" SET DATA TYPE VARCHAR(35),
+                         ADD CONSTRAINT descriptions_language_values
+                            CHECK ("language" ~ '^[A-Za-z]{1,8}(-[A-Za-z0-9]{1,8})*$');
+
+ALTER TABLE version_texts DROP CONSTRAINT version_texts_language_values,
+                          ALTER COLUMN "language" SET DATA TYPE VARCHAR(35),
+                          ADD CONSTRAINT version_texts_language_values
+                            CHECK ("language" ~ '^[A-Za-z]{1,8}(-[A-Za-z0-9]{1,8})*$');
+
+ALTER TABLE dependency_descriptions DROP CONSTRAINT dependency_descriptions_language_values,
+                                    ALTER COLUMN "language" SET DATA TYPE VARCHAR(35),
+                                    ADD CONSTRAINT dependency_descriptions_language_values
+                                        CHECK ("language" ~ '^[A-Za-z]{1,8}(-[A-Za-z0-9]{1,8})*$');
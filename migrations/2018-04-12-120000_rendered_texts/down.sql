@@ -0,0 +1,2 @@
+ALTER TABLE version_texts DROP COLUMN IF EXISTS changes_html,
+                          DROP COLUMN IF EXISTS readme_html;
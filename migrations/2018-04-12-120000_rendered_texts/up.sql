@@ -0,0 +1,2 @@
+ALTER TABLE version_texts ADD COLUMN changes_html TEXT,
+                          ADD COLUMN readme_html TEXT;
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS versions_package_idx;
+DROP INDEX IF EXISTS dependencies_version_idx;
+DROP INDEX IF EXISTS contents_version_idx;
+DROP INDEX IF EXISTS likes_package_idx;
+DROP INDEX IF EXISTS maintainers_package_idx;
@@ -0,0 +1,5 @@
+CREATE INDEX versions_package_idx ON versions (package);
+CREATE INDEX dependencies_version_idx ON dependencies (version);
+CREATE INDEX contents_version_idx ON contents (version);
+CREATE INDEX likes_package_idx ON likes (package);
+CREATE INDEX maintainers_package_idx ON maintainers (package);
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS packages_created_name_idx;
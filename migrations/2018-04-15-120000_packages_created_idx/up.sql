@@ -0,0 +1 @@
+CREATE INDEX packages_created_name_idx ON packages (created DESC, name DESC);
@@ -0,0 +1,4 @@
+DROP TRIGGER IF EXISTS likes_count ON likes;
+DROP FUNCTION IF EXISTS count_likes();
+DROP INDEX IF EXISTS packages_likes_idx;
+ALTER TABLE packages DROP COLUMN IF EXISTS likes;
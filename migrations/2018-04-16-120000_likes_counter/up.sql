@@ -0,0 +1,22 @@
+ALTER TABLE packages ADD COLUMN likes INTEGER NOT NULL DEFAULT 0;
+
+UPDATE packages p SET likes = (SELECT count(*) FROM likes l WHERE l.package = p.name);
+
+CREATE INDEX packages_likes_idx ON packages (likes DESC, name);
+
+CREATE FUNCTION count_likes() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE packages SET likes = likes + 1 WHERE name = NEW.package;
+    END IF;
+
+    IF TG_OP IN ('DELETE', 'UPDATE') THEN
+        UPDATE packages SET likes = likes - 1 WHERE name = OLD.package;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER likes_count AFTER INSERT OR DELETE OR UPDATE OF package ON likes
+    FOR EACH ROW EXECUTE PROCEDURE count_likes();
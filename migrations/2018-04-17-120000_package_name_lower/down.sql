@@ -0,0 +1 @@
+DROP INDEX IF EXISTS packages_name_lower_idx;
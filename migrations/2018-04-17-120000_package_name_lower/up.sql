@@ -0,0 +1,2 @@
+-- package names are unique regardless of case
+CREATE UNIQUE INDEX packages_name_lower_idx ON packages (lower(name));
@@ -0,0 +1,30 @@
+CREATE OR REPLACE FUNCTION count_likes() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE packages SET likes = likes + 1 WHERE name = NEW.package;
+    END IF;
+
+    IF TG_OP IN ('DELETE', 'UPDATE') THEN
+        UPDATE packages SET likes = likes - 1 WHERE name = OLD.package;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TABLE IF EXISTS package_aliases;
+
+ALTER TABLE versions DROP CONSTRAINT versions_package_fk,
+    ADD CONSTRAINT versions_package_fk FOREIGN KEY (package) REFERENCES packages ON DELETE CASCADE;
+
+ALTER TABLE dependencies DROP CONSTRAINT dependencies_package_fk,
+    ADD CONSTRAINT dependencies_package_fk FOREIGN KEY (package) REFERENCES packages ON DELETE CASCADE;
+
+ALTER TABLE descriptions DROP CONSTRAINT descriptions_package_fk,
+    ADD CONSTRAINT descriptions_package_fk FOREIGN KEY (package) REFERENCES packages ON DELETE CASCADE;
+
+ALTER TABLE likes DROP CONSTRAINT likes_package_fk,
+    ADD CONSTRAINT likes_package_fk FOREIGN KEY (package) REFERENCES packages ON DELETE CASCADE;
+
+ALTER TABLE maintainers DROP CONSTRAINT maintainers_package_fk,
+    ADD CONSTRAINT maintainers_package_fk FOREIGN KEY (package) REFERENCES packages ON DELETE CASCADE;
@@ -0,0 +1,48 @@
+ALTER TABLE versions DROP CONSTRAINT versions_package_fk,
+    ADD CONSTRAINT versions_package_fk FOREIGN KEY (package) REFERENCES packages
+        ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE dependencies DROP CONSTRAINT dependencies_package_fk,
+    ADD CONSTRAINT dependencies_package_fk FOREIGN KEY (package) REFERENCES packages
+        ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE descriptions DROP CONSTRAINT descriptions_package_fk,
+    ADD CONSTRAINT descriptions_package_fk FOREIGN KEY (package) REFERENCES packages
+        ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE likes DROP CONSTRAINT likes_package_fk,
+    ADD CONSTRAINT likes_package_fk FOREIGN KEY (package) REFERENCES packages
+        ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE maintainers DROP CONSTRAINT maintainers_package_fk,
+    ADD CONSTRAINT maintainers_package_fk FOREIGN KEY (package) REFERENCES packages
+        ON DELETE CASCADE ON UPDATE CASCADE;
+
+CREATE TABLE package_aliases (
+    name TEXT CONSTRAINT package_aliases_name_pk PRIMARY KEY,
+    package TEXT CONSTRAINT package_aliases_package_fk REFERENCES packages
+        ON DELETE CASCADE ON UPDATE CASCADE NOT NULL,
+    created TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX package_aliases_name_lower_idx ON package_aliases (lower(name));
+CREATE INDEX package_aliases_package_idx ON package_aliases (package);
+
+-- a renamed package keeps its counter, so cascaded updates must not move likes
+CREATE OR REPLACE FUNCTION count_likes() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND NOT EXISTS (SELECT 1 FROM packages WHERE name = OLD.package) THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE packages SET likes = likes + 1 WHERE name = NEW.package;
+    END IF;
+
+    IF TG_OP IN ('DELETE', 'UPDATE') THEN
+        UPDATE packages SET likes = likes - 1 WHERE name = OLD.package;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
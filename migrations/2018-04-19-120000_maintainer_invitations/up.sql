@@ -0,0 +1,9 @@
+CREATE TABLE maintainer_invitations (
+    package TEXT CONSTRAINT maintainer_invitations_package_fk REFERENCES packages
+        ON DELETE CASCADE ON UPDATE CASCADE,
+    "user" INTEGER CONSTRAINT maintainer_invitations_user_fk REFERENCES users ON DELETE CASCADE,
+    invited_by INTEGER CONSTRAINT maintainer_invitations_invited_by_fk REFERENCES users
+        ON DELETE SET NULL,
+    created TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT maintainer_invitations_pk PRIMARY KEY (package, "user")
+);
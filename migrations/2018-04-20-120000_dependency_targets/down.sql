@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS dependencies_package_idx;
+
+ALTER TABLE dependencies DROP CONSTRAINT dependencies_package_fk,
+    ADD CONSTRAINT dependencies_package_fk FOREIGN KEY (package) REFERENCES packages
+        ON DELETE CASCADE ON UPDATE CASCADE;
@@ -0,0 +1,8 @@
+-- dependencies.package names the package depended on, which must not take the
+-- dependency rows of other packages with it when deleted. NO ACTION rather
+-- than RESTRICT, so a package depending on itself can still be deleted.
+ALTER TABLE dependencies DROP CONSTRAINT dependencies_package_fk,
+    ADD CONSTRAINT dependencies_package_fk FOREIGN KEY (package) REFERENCES packages
+        ON DELETE NO ACTION ON UPDATE CASCADE;
+
+CREATE INDEX dependencies_package_idx ON dependencies (package);
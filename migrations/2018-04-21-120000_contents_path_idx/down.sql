@@ -0,0 +1 @@
+DROP INDEX IF EXISTS contents_file_path_idx;
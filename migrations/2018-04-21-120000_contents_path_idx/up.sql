@@ -0,0 +1 @@
+CREATE INDEX contents_file_path_idx ON contents (path) WHERE type = 'file';
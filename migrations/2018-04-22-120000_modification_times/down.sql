@@ -0,0 +1,11 @@
+DROP TRIGGER IF EXISTS catalog_updated ON packages;
+DROP FUNCTION IF EXISTS touch_catalog();
+DROP TABLE IF EXISTS catalog;
+DROP TRIGGER IF EXISTS dependencies_updated ON dependencies;
+DROP FUNCTION IF EXISTS touch_dependent();
+DROP TRIGGER IF EXISTS descriptions_updated ON descriptions;
+DROP TRIGGER IF EXISTS maintainers_updated ON maintainers;
+DROP TRIGGER IF EXISTS versions_updated ON versions;
+DROP FUNCTION IF EXISTS touch_package();
+DROP TRIGGER IF EXISTS packages_updated ON packages;
+DROP FUNCTION IF EXISTS touch_package_row();
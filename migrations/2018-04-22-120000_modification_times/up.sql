@@ -0,0 +1,72 @@
+-- packages.updated moves with everything a package read returns, and
+-- catalog.updated with everything a package list returns, so both can be
+-- served as Last-Modified. clock_timestamp() is read after the row lock is
+-- taken, so concurrent writers store increasing times in commit order.
+
+CREATE FUNCTION touch_package_row() RETURNS TRIGGER AS $$
+BEGIN
+    -- writes that set the time themselves keep it
+    IF NEW IS DISTINCT FROM OLD AND NEW.updated IS NOT DISTINCT FROM OLD.updated THEN
+        NEW.updated := clock_timestamp();
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- also covers likes and downloads, which are counters on the row
+CREATE TRIGGER packages_updated BEFORE UPDATE ON packages
+    FOR EACH ROW EXECUTE PROCEDURE touch_package_row();
+
+CREATE FUNCTION touch_package() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE packages SET updated = clock_timestamp() WHERE name = NEW.package;
+    END IF;
+
+    IF TG_OP = 'DELETE' THEN
+        UPDATE packages SET updated = clock_timestamp() WHERE name = OLD.package;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER versions_updated AFTER INSERT OR DELETE OR UPDATE ON versions
+    FOR EACH ROW EXECUTE PROCEDURE touch_package();
+CREATE TRIGGER maintainers_updated AFTER INSERT OR DELETE OR UPDATE ON maintainers
+    FOR EACH ROW EXECUTE PROCEDURE touch_package();
+CREATE TRIGGER descriptions_updated AFTER INSERT OR DELETE OR UPDATE ON descriptions
+    FOR EACH ROW EXECUTE PROCEDURE touch_package();
+
+-- renaming a package renames it in the dependencies of other packages
+CREATE FUNCTION touch_dependent() RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE packages SET updated = clock_timestamp()
+        WHERE name = (SELECT package FROM versions WHERE id = NEW.version);
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER dependencies_updated AFTER UPDATE OF package ON dependencies
+    FOR EACH ROW EXECUTE PROCEDURE touch_dependent();
+
+-- a single row; deleted packages leave nothing else to date the change by
+CREATE TABLE catalog (
+    id BOOLEAN CONSTRAINT catalog_id_pk PRIMARY KEY DEFAULT TRUE CONSTRAINT catalog_single_row CHECK (id),
+    updated TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO catalog DEFAULT VALUES;
+
+CREATE FUNCTION touch_catalog() RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE catalog SET updated = clock_timestamp();
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER catalog_updated AFTER INSERT OR DELETE OR UPDATE ON packages
+    FOR EACH STATEMENT EXECUTE PROCEDURE touch_catalog();
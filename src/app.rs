@@ -1,32 +1,120 @@
+use std::sync::Arc;
+
 use actix::{Addr, Syn};
 use actix_web::{App};
+use actix_web::dev::ResourceHandler;
 use actix_web::http::Method;
-use actix_web::middleware::Logger;
+use actix_web::middleware::cors::Cors;
 
+use logging::RequestLogger;
+use metrics::Metrics;
+use ratelimit::{RateLimit, RateLimiter};
 use resources;
 use db::DbExecutor;
 use config::Config;
+use config::config_groups::CorsGroup;
 
 #[derive(Clone)]
 pub struct State {
     pub config: Config,
     pub db: Addr<Syn, DbExecutor>,
+    pub limiter: Arc<RateLimiter>,
 }
 
 impl State {
     pub fn new(config: Config, db: Addr<Syn, DbExecutor>) -> State {
-        State { config, db }
+        let limiter = Arc::new(RateLimiter::new(config.rate_limit.clone()));
+
+        State { config, db, limiter }
+    }
+}
+
+/// Builds the CORS middleware, or returns `None` if no origins are allowed.
+fn cors(config: &CorsGroup) -> Option<Cors> {
+    if config.allowed_origins.is_empty() {
+        return None;
+    }
+
+    let mut builder = Cors::build();
+
+    // `*` is only valid on its own, and no listed origins allows any
+    for origin in config.allowed_origins.iter().filter(|x| *x != "*") {
+        builder.allowed_origin(origin);
+    }
+
+    builder.allowed_methods(config.allowed_methods.iter().map(|x| x.as_str()));
+
+    if !config.allowed_headers.is_empty() {
+        builder.allowed_headers(config.allowed_headers.iter().map(|x| x.as_str()));
+    }
+
+    if config.supports_credentials {
+        builder.supports_credentials();
+    }
+
+    Some(builder.max_age(config.max_age).finish())
+}
+
+/// Wraps an API resource definition, adding CORS headers and preflight
+/// handling if enabled.
+fn api<F, R>(cors: &Option<Cors>, f: F) -> impl FnOnce(&mut ResourceHandler<State>) + 'static
+    where F: FnOnce(&mut ResourceHandler<State>) -> R + 'static
+{
+    let cors = cors.clone();
+
+    move |r| {
+        f(r);
+
+        if let Some(cors) = cors {
+            cors.register(r);
+        }
     }
 }
 
 pub fn create(state: State) -> App<State> {
+    let cors = cors(&state.config.cors);
+    let body_limit = state.config.http.max_body_size;
+
     App::with_state(state)
-        .middleware(Logger::default())
-        .resource("/", |r| r.method(Method::GET).f(resources::index))
-        .resource("/api/packages", |r| {
-            r.method(Method::GET).a(resources::list_packages)
+        .middleware(RequestLogger)
+        .middleware(Metrics)
+        .middleware(RateLimit)
+        .resource("/health", |r| {
+            r.name("health");
+            r.method(Method::GET).f(resources::health)
         })
-        .resource("/api/packages/{name}", |r| {
-            r.method(Method::GET).with2(resources::get_package)
+        .resource("/ready", |r| {
+            r.name("ready");
+            r.method(Method::GET).with2(resources::ready)
+        })
+        .resource("/metrics", |r| {
+            r.name("metrics");
+            r.method(Method::GET).with2(resources::metrics)
         })
-}
\ No newline at end of file
+        .resource("/api/version", api(&cors, |r| {
+            r.name("version");
+            r.method(Method::GET).with(resources::version)
+        }))
+        .resource("/api/packages", api(&cors, |r| {
+            r.name("list_packages");
+            r.method(Method::GET).a(resources::list_packages)
+        }))
+        .resource("/api/packages/{name}", api(&cors, |r| {
+            r.name("get_package");
+            r.method(Method::GET).with2(resources::get_package)
+        }))
+        .resource("/api/packages/{name}/versions/{version}/conflicts", api(&cors, |r| {
+            r.name("get_conflicts");
+            r.method(Method::GET).with2(resources::get_conflicts)
+        }))
+        .resource("/api/packages/{name}/maintainers", api(&cors, move |r| {
+            r.name("maintainers");
+            r.method(Method::GET).with2(resources::get_maintainers);
+            // extractors read up to 256 KiB unless told otherwise
+            r.method(Method::POST).with3(resources::add_maintainer).2.limit(body_limit);
+        }))
+        .resource("/api/packages/{name}/maintainers/{username}", api(&cors, |r| {
+            r.name("maintainer");
+            r.method(Method::DELETE).with2(resources::remove_maintainer)
+        }))
+}
@@ -0,0 +1,185 @@
+use std::io;
+
+use actix::{Handler, Message, System};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use failure::{err_msg, Error};
+use rpassword;
+
+use config::Config;
+use config::config_groups::CacheGroup;
+use db::{self, DbExecutor, Traced};
+use db::messages;
+use db::models::types::UserGroup;
+use password;
+
+pub fn app<'a, 'b>() -> App<'a, 'b> {
+    let username = Arg::with_name("username")
+        .required(true)
+        .help("Name of the user");
+
+    App::new("hel2-back")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Hel package repository backend")
+        .arg(Arg::with_name("config")
+            .long("config")
+            .short("c")
+            .value_name("PATH")
+            .help("Config file to use instead of $HEL_CONFIG_PATH or config.toml"))
+        .arg(Arg::with_name("migrate")
+            .long("migrate")
+            .hidden(true))
+        .subcommand(SubCommand::with_name("serve")
+            .about("Starts the HTTP server (the default)"))
+        .subcommand(SubCommand::with_name("migrate")
+            .about("Applies pending database migrations"))
+        .subcommand(SubCommand::with_name("check-config")
+            .about("Validates the configuration"))
+        .subcommand(SubCommand::with_name("create-admin")
+            .about("Creates an administrator account")
+            .arg(username.clone()))
+        .subcommand(SubCommand::with_name("set-group")
+            .about("Changes the group of a user")
+            .arg(username.clone())
+            .arg(Arg::with_name("group")
+                .required(true)
+                .possible_values(&["user", "admin", "banned"])))
+        .subcommand(SubCommand::with_name("reset-password")
+            .about("Sets a new password for a user")
+            .arg(username))
+        .subcommand(SubCommand::with_name("delete-package")
+            .about("Deletes a package with all of its versions")
+            .arg(Arg::with_name("name")
+                .required(true)
+                .help("Name of the package")))
+        .subcommand(SubCommand::with_name("rename-package")
+            .about("Renames a package, keeping the old name as an alias")
+            .arg(Arg::with_name("name")
+                .required(true)
+                .help("Current name of the package"))
+            .arg(Arg::with_name("new-name")
+                .required(true)
+                .help("New name of the package")))
+        // downloads are only kept as a counter, with no records to recount
+        // them from, so there is no recount-downloads
+        .subcommand(SubCommand::with_name("recount-likes")
+            .about("Recomputes like counters of all packages"))
+}
+
+/// Runs an operator command. Returns `None` if the server should be started
+/// instead.
+pub fn run(matches: &ArgMatches, config: &Config) -> Option<Result<(), Error>> {
+    if matches.is_present("migrate") {
+        return Some(migrate(config));
+    }
+
+    let result = match matches.subcommand() {
+        ("migrate", _) => migrate(config),
+        ("check-config", _) => {
+            println!("configuration is valid");
+            Ok(())
+        }
+        ("create-admin", Some(args)) => create_admin(config, args.value_of("username").unwrap()),
+        ("set-group", Some(args)) => set_group(
+            config,
+            args.value_of("username").unwrap(),
+            args.value_of("group").unwrap(),
+        ),
+        ("reset-password", Some(args)) => {
+            reset_password(config, args.value_of("username").unwrap())
+        }
+        ("delete-package", Some(args)) => send(config, messages::DeletePackage(
+            args.value_of("name").unwrap().to_string(),
+        )),
+        ("rename-package", Some(args)) => send(config, messages::RenamePackage {
+            name: args.value_of("name").unwrap().to_string(),
+            new_name: args.value_of("new-name").unwrap().to_string(),
+        }),
+        ("recount-likes", _) => recount_likes(config),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+pub fn migrate(config: &Config) -> Result<(), Error> {
+    let conn = db::establish_connection(&config.database.url)?;
+    let version = db::run_migrations(&conn, &mut io::stdout())?;
+
+    println!("schema version: {}", version.as_deref().unwrap_or("none"));
+
+    Ok(())
+}
+
+fn recount_likes(config: &Config) -> Result<(), Error> {
+    let fixed = send(config, messages::RecountLikes)?;
+
+    println!("fixed like counters of {} packages", fixed);
+
+    Ok(())
+}
+
+fn create_admin(config: &Config, username: &str) -> Result<(), Error> {
+    let password = prompt_password()?;
+    let salt = password::generate_salt()?;
+
+    let id = send(config, messages::CreateUser {
+        username: username.to_string(),
+        password: password::hash(&password, &salt),
+        salt,
+        group: UserGroup::Admin,
+    })?;
+
+    println!("created admin {} with id {}", username, id);
+
+    Ok(())
+}
+
+fn set_group(config: &Config, username: &str, group: &str) -> Result<(), Error> {
+    send(config, messages::SetUserGroup {
+        username: username.to_string(),
+        group: group.parse()?,
+    })
+}
+
+fn reset_password(config: &Config, username: &str) -> Result<(), Error> {
+    let password = prompt_password()?;
+    let salt = password::generate_salt()?;
+
+    send(config, messages::SetPassword {
+        username: username.to_string(),
+        password: password::hash(&password, &salt),
+        salt,
+    })
+}
+
+fn prompt_password() -> Result<String, Error> {
+    let password = rpassword::prompt_password_stderr("Password: ")?;
+
+    if password.is_empty() {
+        return Err(err_msg("password must not be empty"));
+    }
+
+    if rpassword::prompt_password_stderr("Repeat password: ")? != password {
+        return Err(err_msg("passwords do not match"));
+    }
+
+    Ok(password)
+}
+
+/// Runs a single message through a temporary database executor.
+fn send<M, T>(config: &Config, msg: M) -> Result<T, Error>
+    where M: Message<Result = Result<T, Error>> + Send + 'static,
+          T: Send + 'static,
+          DbExecutor: Handler<Traced<M>>,
+{
+    let mut sys = System::new("hel2-back-cli");
+    // a single message has no use for a cache; servers poll for its writes
+    let cache = CacheGroup {
+        packages: 0,
+        pages: 0,
+        ..config.cache.clone()
+    };
+    let db = db::start(&config.database, &cache, &config.names, 1);
+
+    sys.run_until_complete(db::send(&db, None, msg))?
+}
@@ -1,35 +1,703 @@
-use std::io::Read;
+use std::env;
+use std::io::{self, Read};
 use std::fs::File;
+use std::net::ToSocketAddrs;
 use std::path::Path;
 
+use actix_web::http::{Method, Uri};
+use actix_web::http::header::HeaderName;
 use failure::Error;
+use toml::Value;
+use toml::value::Table;
+
+use error::ConfigError;
+
+/// Prefix of environment variables overriding config fields, e.g.
+/// `HEL_DATABASE_URL` overrides `url` in the `[database]` group.
+const ENV_PREFIX: &str = "HEL_";
+
+/// Names of the config groups, used to map environment variables to fields.
+const GROUPS: &[&str] = &["database", "http", "log", "cors", "rate_limit", "cache", "names"];
+
+/// Variables with the `HEL_` prefix that aren't config fields.
+const ENV_OTHER: &[&str] = &["HEL_CONFIG_PATH"];
 
 pub mod config_groups {
+    use std::collections::HashMap;
+
     #[derive(Debug, Clone, Deserialize)]
+    #[serde(deny_unknown_fields)]
     pub struct DbGroup {
         pub url: String,
+        #[serde(default = "DbGroup::default_threads")]
         pub threads: usize,
+        /// Maximum number of pooled connections, `threads` by default.
+        #[serde(default)]
+        pub pool_size: Option<u32>,
+        /// How long to wait for a pooled connection, in milliseconds.
+        #[serde(default = "DbGroup::default_acquire_timeout")]
+        pub acquire_timeout: u64,
+        /// Connections older than this many seconds are closed.
+        #[serde(default)]
+        pub max_lifetime: Option<u64>,
+        /// Connections idle for this many seconds are closed.
+        #[serde(default)]
+        pub idle_timeout: Option<u64>,
+        /// Apply pending migrations before starting the server.
+        #[serde(default)]
+        pub migrate_on_startup: bool,
+        /// How long `/ready` waits for the database, in milliseconds.
+        #[serde(default = "DbGroup::default_ready_timeout")]
+        pub ready_timeout: u64,
+    }
+
+    impl DbGroup {
+        fn default_threads() -> usize {
+            4
+        }
+
+        fn default_acquire_timeout() -> u64 {
+            5000
+        }
+
+        fn default_ready_timeout() -> u64 {
+            2000
+        }
     }
 
     #[derive(Debug, Clone, Deserialize)]
+    #[serde(deny_unknown_fields)]
     pub struct HttpGroup {
+        #[serde(default = "HttpGroup::default_pagination_limit")]
         pub pagination_limit: u32,
+        #[serde(default)]
         pub threads: Option<usize>,
+        #[serde(default = "HttpGroup::default_bind_address")]
         pub bind_address: String,
+        /// Seconds to keep idle connections open, 0 disables keep-alive.
+        #[serde(default = "HttpGroup::default_keep_alive")]
+        pub keep_alive: usize,
+        /// Seconds in-flight requests and database messages are given to
+        /// finish after SIGTERM.
+        #[serde(default = "HttpGroup::default_shutdown_timeout")]
+        pub shutdown_timeout: u16,
+        /// How long a request may wait for the database, in milliseconds.
+        #[serde(default = "HttpGroup::default_request_timeout")]
+        pub request_timeout: u64,
+        /// Maximum size of a request body, in bytes. Every route reading a
+        /// body has to apply it, as actix has no server-wide limit.
+        #[serde(default = "HttpGroup::default_max_body_size")]
+        pub max_body_size: usize,
+        /// PEM certificate chain; serves HTTPS when set with `tls_key`.
+        #[serde(default)]
+        pub tls_cert: Option<String>,
+        /// PEM private key for `tls_cert`.
+        #[serde(default)]
+        pub tls_key: Option<String>,
+        /// Plain HTTP address redirecting to HTTPS, if TLS is enabled.
+        #[serde(default)]
+        pub redirect_address: Option<String>,
+    }
+
+    impl HttpGroup {
+        fn default_pagination_limit() -> u32 {
+            50
+        }
+
+        fn default_bind_address() -> String {
+            "127.0.0.1:8080".to_string()
+        }
+
+        pub fn tls_enabled(&self) -> bool {
+            self.tls_cert.is_some() || self.tls_key.is_some()
+        }
+
+        fn default_keep_alive() -> usize {
+            5
+        }
+
+        fn default_shutdown_timeout() -> u16 {
+            30
+        }
+
+        fn default_request_timeout() -> u64 {
+            30_000
+        }
+
+        fn default_max_body_size() -> usize {
+            256 * 1024
+        }
+    }
+
+    impl Default for HttpGroup {
+        fn default() -> HttpGroup {
+            HttpGroup {
+                pagination_limit: HttpGroup::default_pagination_limit(),
+                threads: None,
+                bind_address: HttpGroup::default_bind_address(),
+                keep_alive: HttpGroup::default_keep_alive(),
+                shutdown_timeout: HttpGroup::default_shutdown_timeout(),
+                request_timeout: HttpGroup::default_request_timeout(),
+                max_body_size: HttpGroup::default_max_body_size(),
+                tls_cert: None,
+                tls_key: None,
+                redirect_address: None,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct CorsGroup {
+        /// Origins allowed to call the API, or only `*` for any. CORS is
+        /// disabled when empty.
+        #[serde(default)]
+        pub allowed_origins: Vec<String>,
+        #[serde(default = "CorsGroup::default_allowed_methods")]
+        pub allowed_methods: Vec<String>,
+        /// Request headers the origins may send, any if empty.
+        #[serde(default)]
+        pub allowed_headers: Vec<String>,
+        #[serde(default)]
+        pub supports_credentials: bool,
+        /// How long browsers may cache preflight responses, in seconds.
+        #[serde(default = "CorsGroup::default_max_age")]
+        pub max_age: usize,
+    }
+
+    impl CorsGroup {
+        fn default_allowed_methods() -> Vec<String> {
+            ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE"].iter()
+                .map(|x| x.to_string())
+                .collect()
+        }
+
+        fn default_max_age() -> usize {
+            3600
+        }
+    }
+
+    impl Default for CorsGroup {
+        fn default() -> CorsGroup {
+            CorsGroup {
+                allowed_origins: Vec::new(),
+                allowed_methods: CorsGroup::default_allowed_methods(),
+                allowed_headers: Vec::new(),
+                supports_credentials: false,
+                max_age: CorsGroup::default_max_age(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct CacheGroup {
+        /// Number of cached packages, 0 disables the cache.
+        #[serde(default = "CacheGroup::default_packages")]
+        pub packages: usize,
+        /// Number of cached package list pages, 0 disables the cache.
+        #[serde(default = "CacheGroup::default_pages")]
+        pub pages: usize,
+        /// Seconds after which cached entries are reloaded.
+        #[serde(default = "CacheGroup::default_ttl")]
+        pub ttl: u64,
+        /// Seconds between checks for changes made by other processes, such
+        /// as other servers and the CLI, which are served from the cache
+        /// until then. 0 disables the checks, leaving such changes to `ttl`.
+        #[serde(default = "CacheGroup::default_poll")]
+        pub poll: u64,
+    }
+
+    impl CacheGroup {
+        fn default_packages() -> usize {
+            1000
+        }
+
+        fn default_pages() -> usize {
+            100
+        }
+
+        fn default_ttl() -> u64 {
+            60
+        }
+
+        fn default_poll() -> u64 {
+            1
+        }
+
+        /// Whether anything is cached at all.
+        pub fn enabled(&self) -> bool {
+            self.packages > 0 || self.pages > 0
+        }
+    }
+
+    impl Default for CacheGroup {
+        fn default() -> CacheGroup {
+            CacheGroup {
+                packages: CacheGroup::default_packages(),
+                pages: CacheGroup::default_pages(),
+                ttl: CacheGroup::default_ttl(),
+                poll: CacheGroup::default_poll(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct NamesGroup {
+        /// Names no package may take, compared case-insensitively.
+        #[serde(default = "NamesGroup::default_reserved")]
+        pub reserved: Vec<String>,
+        #[serde(default = "NamesGroup::default_max_length")]
+        pub max_length: usize,
+        /// Maximum edit distance at which a new name is rejected as too
+        /// similar to an existing one, 0 disables the check.
+        #[serde(default = "NamesGroup::default_similarity")]
+        pub similarity: usize,
+    }
+
+    impl NamesGroup {
+        fn default_reserved() -> Vec<String> {
+            ["hel", "admin", "api", "root", "system", "openos"]
+                .iter()
+                .map(|x| x.to_string())
+                .collect()
+        }
+
+        fn default_max_length() -> usize {
+            64
+        }
+
+        fn default_similarity() -> usize {
+            1
+        }
+    }
+
+    impl Default for NamesGroup {
+        fn default() -> NamesGroup {
+            NamesGroup {
+                reserved: NamesGroup::default_reserved(),
+                max_length: NamesGroup::default_max_length(),
+                similarity: NamesGroup::default_similarity(),
+            }
+        }
+    }
+
+    /// A token bucket: `burst` requests at once, refilled at `rate` requests
+    /// per second.
+    #[derive(Debug, Clone, Copy, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct Limit {
+        pub rate: f64,
+        pub burst: u32,
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct RateLimitGroup {
+        #[serde(default = "RateLimitGroup::default_enabled")]
+        pub enabled: bool,
+        /// Take the client address from `Forwarded`/`X-Forwarded-For`. Only
+        /// enable behind a reverse proxy that sets these headers.
+        #[serde(default)]
+        pub trust_proxy: bool,
+        /// Limit of routes not listed in `routes`.
+        #[serde(default = "RateLimitGroup::default_limit")]
+        pub default: Limit,
+        /// Limits by route name.
+        #[serde(default)]
+        pub routes: HashMap<String, Limit>,
+    }
+
+    impl RateLimitGroup {
+        /// Name under which credential checks are limited per client address.
+        pub const CREDENTIALS: &'static str = "credentials";
+
+        fn default_enabled() -> bool {
+            true
+        }
+
+        fn default_limit() -> Limit {
+            Limit { rate: 10.0, burst: 50 }
+        }
+
+        /// Returns the limit of a route. Credential checks are limited to 5
+        /// per minute unless configured otherwise, to slow down password
+        /// guessing.
+        pub fn limit(&self, route: &str) -> Limit {
+            match self.routes.get(route) {
+                Some(limit) => *limit,
+                None if route == RateLimitGroup::CREDENTIALS => {
+                    Limit { rate: 1.0 / 12.0, burst: 5 }
+                }
+                None => self.default,
+            }
+        }
+    }
+
+    impl Default for RateLimitGroup {
+        fn default() -> RateLimitGroup {
+            RateLimitGroup {
+                enabled: RateLimitGroup::default_enabled(),
+                trust_proxy: false,
+                default: RateLimitGroup::default_limit(),
+                routes: HashMap::new(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum LogFormat {
+        Text,
+        Json,
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct LogGroup {
+        #[serde(default = "LogGroup::default_format")]
+        pub format: LogFormat,
+        /// `env_logger` filter directives; `RUST_LOG` takes precedence.
+        #[serde(default = "LogGroup::default_filter")]
+        pub filter: String,
+    }
+
+    impl LogGroup {
+        fn default_format() -> LogFormat {
+            LogFormat::Text
+        }
+
+        fn default_filter() -> String {
+            "info".to_string()
+        }
+    }
+
+    impl Default for LogGroup {
+        fn default() -> LogGroup {
+            LogGroup {
+                format: LogGroup::default_format(),
+                filter: LogGroup::default_filter(),
+            }
+        }
     }
 }
 
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub database: config_groups::DbGroup,
+    #[serde(default)]
     pub http: config_groups::HttpGroup,
+    #[serde(default)]
+    pub log: config_groups::LogGroup,
+    #[serde(default)]
+    pub cors: config_groups::CorsGroup,
+    #[serde(default)]
+    pub rate_limit: config_groups::RateLimitGroup,
+    #[serde(default)]
+    pub cache: config_groups::CacheGroup,
+    #[serde(default)]
+    pub names: config_groups::NamesGroup,
 }
 
 impl Config {
+    /// Loads the config file, applies `HEL_*` environment overrides and
+    /// validates the result.
+    ///
+    /// A missing file is treated as empty, so the config may come entirely
+    /// from the environment.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, Error> {
+        let path = path.as_ref();
+        let mut table = match File::open(path) {
+            Ok(f) => Config::read_table(path, f)?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Table::new(),
+            Err(e) => return Err(ConfigError::Read {
+                path: path.display().to_string(),
+                cause: e.to_string(),
+            }.into()),
+        };
+
+        Config::apply_env(&mut table, env::vars())?;
+
+        let config: Config = Value::Table(table).try_into()
+            .map_err(|e| ConfigError::Invalid { reason: e.to_string() })?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn read_table(path: &Path, mut f: File) -> Result<Table, ConfigError> {
         let mut s = String::with_capacity(2048);
-        let mut f = File::open(path)?;
-        f.read_to_string(&mut s)?;
-        Ok(::toml::from_str(&s)?)
+
+        f.read_to_string(&mut s).map_err(|e| ConfigError::Read {
+            path: path.display().to_string(),
+            cause: e.to_string(),
+        })?;
+
+        ::toml::from_str(&s).map_err(|e| ConfigError::Parse {
+            path: path.display().to_string(),
+            cause: e.to_string(),
+        })
+    }
+
+    /// Sets fields named by `HEL_<GROUP>_<FIELD>` variables. Variables whose
+    /// group is unknown are skipped: container platforms add their own, such
+    /// as `HEL_SERVICE_HOST` for a Kubernetes service named `hel`.
+    fn apply_env<I>(table: &mut Table, vars: I) -> Result<(), ConfigError>
+        where I: Iterator<Item=(String, String)>
+    {
+        for (name, raw) in vars {
+            let (group, key) = match Config::env_target(&name) {
+                Some(target) => target,
+                None => continue,
+            };
+
+            let group_table = table.entry(group.to_string())
+                .or_insert_with(|| Value::Table(Table::new()));
+
+            match *group_table {
+                Value::Table(ref mut group_table) => {
+                    group_table.insert(key, Config::parse_env_value(&raw));
+                }
+                _ => return Err(ConfigError::Invalid {
+                    reason: format!("`{}` must be a table", group),
+                }),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the group and field a variable overrides, if it names one.
+    fn env_target(name: &str) -> Option<(&'static str, String)> {
+        if !name.starts_with(ENV_PREFIX) || ENV_OTHER.contains(&name) {
+            return None;
+        }
+
+        let rest = name[ENV_PREFIX.len()..].to_lowercase();
+
+        GROUPS.iter()
+            .find(|group| rest.starts_with(&format!("{}_", group)))
+            .map(|group| (*group, rest[group.len() + 1..].to_string()))
+            .filter(|(_, key)| !key.is_empty())
+    }
+
+    /// Returns the `HEL_` variables of the environment that `load` skipped,
+    /// so that typos can be reported once logging is set up.
+    pub fn ignored_env() -> Vec<String> {
+        env::vars()
+            .map(|(name, _)| name)
+            .filter(|name| name.starts_with(ENV_PREFIX) && !ENV_OTHER.contains(&name.as_str()))
+            .filter(|name| Config::env_target(name).is_none())
+            .collect()
+    }
+
+    /// Parses an environment variable as a TOML value, treating anything
+    /// that is not valid TOML (e.g. a URL) as a plain string.
+    fn parse_env_value(raw: &str) -> Value {
+        format!("v = {}", raw).parse::<Value>().ok()
+            .and_then(|x| match x {
+                Value::Table(mut t) => t.remove("v"),
+                _ => None,
+            })
+            .unwrap_or_else(|| Value::String(raw.to_string()))
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |reason: &str| Err(ConfigError::Invalid { reason: reason.to_string() });
+
+        if !self.database.url.starts_with("postgres://")
+            && !self.database.url.starts_with("postgresql://") {
+            return invalid("database.url must be a postgres:// URL");
+        }
+
+        if self.database.threads == 0 {
+            return invalid("database.threads must be greater than zero");
+        }
+
+        if self.database.pool_size == Some(0) {
+            return invalid("database.pool_size must be greater than zero");
+        }
+
+        if self.database.acquire_timeout == 0 {
+            return invalid("database.acquire_timeout must be greater than zero");
+        }
+
+        if self.http.threads == Some(0) {
+            return invalid("http.threads must be greater than zero");
+        }
+
+        if self.http.pagination_limit == 0 {
+            return invalid("http.pagination_limit must be greater than zero");
+        }
+
+        if self.http.request_timeout == 0 {
+            return invalid("http.request_timeout must be greater than zero");
+        }
+
+        if self.http.max_body_size == 0 {
+            return invalid("http.max_body_size must be greater than zero");
+        }
+
+        if self.http.bind_address.to_socket_addrs().is_err() {
+            return invalid("http.bind_address must be a host:port pair");
+        }
+
+        if self.http.tls_cert.is_some() != self.http.tls_key.is_some() {
+            return invalid("http.tls_cert and http.tls_key must be set together");
+        }
+
+        if self.http.tls_enabled() && !cfg!(feature = "tls") {
+            return invalid("http.tls_cert is set, but the server is built without TLS support");
+        }
+
+        if let Some(ref address) = self.http.redirect_address {
+            if !self.http.tls_enabled() {
+                return invalid("http.redirect_address requires TLS to be enabled");
+            }
+
+            if address.to_socket_addrs().is_err() {
+                return invalid("http.redirect_address must be a host:port pair");
+            }
+        }
+
+        self.validate_cors()?;
+
+        if self.names.max_length < ::validate::MIN_NAME_LENGTH {
+            return Err(ConfigError::Invalid {
+                reason: format!("names.max_length must be at least {}",
+                                ::validate::MIN_NAME_LENGTH),
+            });
+        }
+
+        let limits = self.rate_limit.routes.iter()
+            .map(|(route, limit)| (route.as_str(), limit))
+            .chain(Some(("default", &self.rate_limit.default)));
+
+        for (route, limit) in limits {
+            if limit.rate.is_nan() || limit.rate <= 0.0 || limit.burst == 0 {
+                return Err(ConfigError::Invalid {
+                    reason: format!("rate_limit: `{}` needs a positive rate and burst", route),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks what `Cors` would otherwise panic on when the app is built.
+    fn validate_cors(&self) -> Result<(), ConfigError> {
+        let invalid = |reason: String| Err(ConfigError::Invalid { reason });
+        let cors = &self.cors;
+
+        if cors.allowed_origins.len() > 1 && cors.allowed_origins.iter().any(|x| x == "*") {
+            return invalid("cors.allowed_origins: `*` allows any origin and cannot be \
+                            combined with other origins".into());
+        }
+
+        for origin in &cors.allowed_origins {
+            if origin == "*" {
+                if cors.supports_credentials {
+                    return invalid(
+                        "cors.supports_credentials cannot be used with the `*` origin".into()
+                    );
+                }
+            } else if origin.parse::<Uri>().is_err() {
+                return invalid(format!("cors.allowed_origins: invalid origin `{}`", origin));
+            }
+        }
+
+        for method in &cors.allowed_methods {
+            if Method::from_bytes(method.as_bytes()).is_err() {
+                return invalid(format!("cors.allowed_methods: invalid method `{}`", method));
+            }
+        }
+
+        for header in &cors.allowed_headers {
+            if HeaderName::from_bytes(header.as_bytes()).is_err() {
+                return invalid(format!("cors.allowed_headers: invalid header `{}`", header));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect()
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parse_env_value_types() {
+        assert_eq!(Config::parse_env_value("8"), Value::Integer(8));
+        assert_eq!(Config::parse_env_value("true"), Value::Boolean(true));
+        assert_eq!(Config::parse_env_value("[\"a\", \"b\"]"), Value::Array(vec![
+            Value::String("a".into()),
+            Value::String("b".into()),
+        ]));
+        assert_eq!(Config::parse_env_value("\"quoted\""), Value::String("quoted".into()));
+        assert_eq!(Config::parse_env_value("postgres://u:p@localhost/hel"),
+                   Value::String("postgres://u:p@localhost/hel".into()));
+        assert_eq!(Config::parse_env_value(""), Value::String("".into()));
+    }
+
+    #[test]
+    fn apply_env_overrides_fields() {
+        let mut table: Table = ::toml::from_str("[database]\nurl = \"postgres://file\"").unwrap();
+
+        Config::apply_env(&mut table, vars(&[
+            ("PATH", "/bin"),
+            ("HEL_CONFIG_PATH", "other.toml"),
+            ("HEL_DATABASE_URL", "postgres://env"),
+            ("HEL_RATE_LIMIT_TRUST_PROXY", "true"),
+            ("HEL_HTTP_THREADS", "2"),
+        ]).into_iter()).unwrap();
+
+        let config: Config = Value::Table(table).try_into().unwrap();
+        assert_eq!(config.database.url, "postgres://env");
+        assert!(config.rate_limit.trust_proxy);
+        assert_eq!(config.http.threads, Some(2));
+    }
+
+    #[test]
+    fn apply_env_skips_unknown_groups() {
+        let mut table = Table::new();
+
+        Config::apply_env(&mut table, vars(&[
+            ("HEL_SERVICE_HOST", "10.0.0.1"),
+            ("HEL_PORT", "tcp://10.0.0.1:8080"),
+            ("HEL_PORT_8080_TCP", "tcp://10.0.0.1:8080"),
+            ("HEL_DATABSE_URL", "x"),
+            ("HEL_DATABASE", "x"),
+            ("HEL_DATABASE_", "x"),
+        ]).into_iter()).unwrap();
+
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let mut table: Table = ::toml::from_str("[database]\nurl = \"postgres://file\"").unwrap();
+        Config::apply_env(&mut table, vars(&[("HEL_DATABASE_URLL", "x")]).into_iter()).unwrap();
+        assert!(Value::Table(table).try_into::<Config>().is_err());
+
+        let table: Table = ::toml::from_str("[database]\nurl = \"postgres://file\"\n\
+                                             [http]\nbind_adress = \"0.0.0.0:80\"").unwrap();
+        assert!(Value::Table(table).try_into::<Config>().is_err());
+    }
+
+    #[test]
+    fn cors_any_origin_is_exclusive() {
+        let mut config: Config = ::toml::from_str("[database]\nurl = \"postgres://file\"\n\
+                                                   [cors]\nallowed_origins = [\"*\"]").unwrap();
+        assert!(config.validate_cors().is_ok());
+
+        config.cors.allowed_origins.push("https://hel.example".into());
+        assert!(config.validate_cors().is_err());
+    }
+}
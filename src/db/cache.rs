@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDateTime;
+
+use config::config_groups::CacheGroup;
+use models::package;
+
+/// A page of the package list, with the time the list last changed.
+type Page = (Vec<package::Short>, NaiveDateTime);
+
+/// A map whose entries expire after a fixed time. When full, the oldest
+/// entry is evicted.
+struct TtlMap<K, V> {
+    entries: HashMap<K, (Instant, V)>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> TtlMap<K, V> {
+    fn new(capacity: usize, ttl: Duration) -> TtlMap<K, V> {
+        TtlMap {
+            entries: HashMap::new(),
+            capacity,
+            ttl,
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        let expired = match self.entries.get(key) {
+            Some(&(inserted, ref value)) if inserted.elapsed() < self.ttl => {
+                return Some(value.clone());
+            }
+            Some(_) => true,
+            None => false,
+        };
+
+        if expired {
+            self.entries.remove(key);
+        }
+
+        None
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let ttl = self.ttl;
+            self.entries.retain(|_, &mut (inserted, _)| inserted.elapsed() < ttl);
+        }
+
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self.entries.iter()
+                .min_by_key(|&(_, &(inserted, _))| inserted)
+                .map(|(key, _)| key.clone());
+
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.entries.insert(key, (Instant::now(), value));
+    }
+
+    fn remove(&mut self, key: &K) {
+        self.entries.remove(key);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Results of package reads, shared by all database executors.
+///
+/// Writes bump a generation counter before invalidating, and reads only
+/// store results if no write happened since they started, so a read racing
+/// with a write cannot cache stale data. Writes by other processes are only
+/// noticed when the catalog is next polled, see `cache.poll`.
+pub struct Cache {
+    generation: AtomicUsize,
+    packages: Mutex<TtlMap<String, package::Full>>,
+    pages: Mutex<TtlMap<(u32, u32), Page>>,
+}
+
+impl Cache {
+    pub fn new(config: &CacheGroup) -> Cache {
+        let ttl = Duration::from_secs(config.ttl);
+
+        Cache {
+            generation: AtomicUsize::new(0),
+            packages: Mutex::new(TtlMap::new(config.packages, ttl)),
+            pages: Mutex::new(TtlMap::new(config.pages, ttl)),
+        }
+    }
+
+    /// Returns the generation to pass to `put_*` after loading from the
+    /// database.
+    pub fn generation(&self) -> usize {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Looks a package up by its name in any case.
+    pub fn package(&self, name: &str) -> Option<package::Full> {
+        self.packages.lock().unwrap_or_else(|e| e.into_inner()).get(&name.to_lowercase())
+    }
+
+    pub fn put_package(&self, generation: usize, package: &package::Full) {
+        let mut packages = self.packages.lock().unwrap_or_else(|e| e.into_inner());
+
+        if self.generation() == generation {
+            packages.insert(package.name.to_lowercase(), package.clone());
+        }
+    }
+
+    pub fn page(&self, page: u32, limit: u32) -> Option<Page> {
+        self.pages.lock().unwrap_or_else(|e| e.into_inner()).get(&(page, limit))
+    }
+
+    pub fn put_page(&self, generation: usize, page: u32, limit: u32,
+                    packages: &[package::Short], updated: NaiveDateTime) {
+        let mut pages = self.pages.lock().unwrap_or_else(|e| e.into_inner());
+
+        if self.generation() == generation {
+            pages.insert((page, limit), (packages.to_vec(), updated));
+        }
+    }
+
+    /// Drops everything cached about a package, including every page of the
+    /// package list. Call after the write is committed.
+    pub fn invalidate(&self, name: &str) {
+        let mut packages = self.packages.lock().unwrap_or_else(|e| e.into_inner());
+        let mut pages = self.pages.lock().unwrap_or_else(|e| e.into_inner());
+
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        packages.remove(&name.to_lowercase());
+        pages.clear();
+    }
+
+    /// Drops everything cached. Call after writes that also change other
+    /// packages, such as renames, which rewrite the dependencies of their
+    /// dependents.
+    pub fn clear(&self) {
+        let mut packages = self.packages.lock().unwrap_or_else(|e| e.into_inner());
+        let mut pages = self.pages.lock().unwrap_or_else(|e| e.into_inner());
+
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        packages.clear();
+        pages.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn config(ttl: u64) -> CacheGroup {
+        CacheGroup {
+            packages: 2,
+            pages: 2,
+            ttl,
+            poll: 0,
+        }
+    }
+
+    fn package(name: &str) -> package::Full {
+        let date = NaiveDate::from_ymd(2018, 4, 1).and_hms(12, 0, 0);
+
+        package::Full {
+            name: name.to_string(),
+            description: vec![],
+            website: String::new(),
+            license: String::new(),
+            authors: vec![],
+            maintainers: vec![],
+            versions: vec![],
+            downloads: 0,
+            likes: 0,
+            created: date,
+            updated: date,
+        }
+    }
+
+    #[test]
+    fn entries_expire() {
+        let mut map = TtlMap::new(2, Duration::from_millis(20));
+        map.insert("a", 1);
+        assert_eq!(map.get(&"a"), Some(1));
+
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(map.get(&"a"), None);
+        assert!(map.entries.is_empty());
+    }
+
+    #[test]
+    fn oldest_entry_is_evicted() {
+        let mut map = TtlMap::new(2, Duration::from_secs(60));
+        map.insert("a", 1);
+        thread::sleep(Duration::from_millis(1));
+        map.insert("b", 2);
+        thread::sleep(Duration::from_millis(1));
+        map.insert("c", 3);
+
+        assert_eq!(map.get(&"a"), None);
+        assert_eq!(map.get(&"b"), Some(2));
+        assert_eq!(map.get(&"c"), Some(3));
+
+        // replacing an entry doesn't evict another
+        map.insert("c", 4);
+        assert_eq!(map.get(&"b"), Some(2));
+        assert_eq!(map.get(&"c"), Some(4));
+    }
+
+    #[test]
+    fn zero_capacity_caches_nothing() {
+        let mut map = TtlMap::new(0, Duration::from_secs(60));
+        map.insert("a", 1);
+        assert_eq!(map.get(&"a"), None);
+    }
+
+    #[test]
+    fn packages_are_found_in_any_case() {
+        let cache = Cache::new(&config(60));
+        cache.put_package(cache.generation(), &package("Name"));
+
+        assert_eq!(cache.package("nAME").unwrap().name, "Name");
+    }
+
+    #[test]
+    fn reads_racing_with_writes_are_not_cached() {
+        let cache = Cache::new(&config(60));
+        let date = NaiveDate::from_ymd(2018, 4, 1).and_hms(12, 0, 0);
+
+        // a read starts, a write invalidates, and the read finishes
+        let generation = cache.generation();
+        cache.invalidate("name");
+        cache.put_package(generation, &package("name"));
+        cache.put_page(generation, 1, 10, &[], date);
+        assert!(cache.package("name").is_none());
+        assert!(cache.page(1, 10).is_none());
+
+        let generation = cache.generation();
+        cache.clear();
+        cache.put_package(generation, &package("name"));
+        assert!(cache.package("name").is_none());
+
+        // reads starting after the write are cached
+        let generation = cache.generation();
+        cache.put_package(generation, &package("name"));
+        cache.put_page(generation, 1, 10, &[], date);
+        assert!(cache.package("name").is_some());
+        assert_eq!(cache.page(1, 10).unwrap().1, date);
+    }
+
+    #[test]
+    fn invalidating_drops_the_package_and_every_page() {
+        let cache = Cache::new(&config(60));
+        let date = NaiveDate::from_ymd(2018, 4, 1).and_hms(12, 0, 0);
+        let generation = cache.generation();
+        cache.put_package(generation, &package("first"));
+        cache.put_package(generation, &package("second"));
+        cache.put_page(generation, 1, 10, &[], date);
+
+        cache.invalidate("FIRST");
+        assert!(cache.package("first").is_none());
+        assert!(cache.package("second").is_some());
+        assert!(cache.page(1, 10).is_none());
+    }
+}
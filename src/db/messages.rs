@@ -1,18 +1,69 @@
 use std::collections::HashMap;
+use std::mem;
 
 use actix::{Message, Handler};
+use chrono::NaiveDateTime;
 use diesel::{self, insert_into};
-use diesel::sql_types::{BigInt, Text};
+use diesel::pg::PgConnection;
+use diesel::sql_types::{Integer, Text};
 use diesel::prelude::*;
 use failure::Error;
+use serde_json;
 
+use ::config::config_groups::NamesGroup;
+use ::error::{ContentErrors, MaintainerError, ValidationError};
+use ::markdown;
+use ::metrics;
+use ::password;
 use ::models::*;
-use super::DbExecutor;
+use ::validate;
+use super::{DbExecutor, Traced};
 use super::models;
 use super::schema;
 
+/// Implements `Handler<Traced<M>>` for the listed messages.
+///
+/// A blanket impl over every `M` handled by `DbExecutor` would make the
+/// trait solver recurse through `Traced<Traced<...>>`, hence the macro.
+macro_rules! traced {
+    ($($msg:ty),* $(,)*) => {
+        $(
+            impl Handler<Traced<$msg>> for DbExecutor {
+                type Result = <$msg as Message>::Result;
+
+                fn handle(&mut self, msg: Traced<$msg>, ctx: &mut Self::Context) -> Self::Result {
+                    super::handle_traced(self, msg, ctx)
+                }
+            }
+        )*
+    };
+}
+
 sql_function!(lower, lower_t, (s: Text) -> Text);
 
+traced!(
+    GetPackage,
+    GetUser,
+    GetPackages,
+    GetPackagesAfter,
+    CreatePackage,
+    CreateUser,
+    GetConflicts,
+    DeletePackage,
+    RenamePackage,
+    Authenticate,
+    GetMaintainers,
+    InviteMaintainer,
+    AcceptInvitation,
+    RemoveMaintainer,
+    RecountLikes,
+    SetUserGroup,
+    SetPassword,
+    Ping,
+    GetSchemaVersion,
+    GetCounts,
+);
+
 pub struct GetPackage(pub String);
 
 impl Message for GetPackage {
@@ -23,122 +74,135 @@ impl Handler<GetPackage> for DbExecutor {
     type Result = Result<package::Full, Error>;
 
     fn handle(&mut self, msg: GetPackage, _: &mut Self::Context) -> Self::Result {
-        let name = &msg.0;
-
-        let package: models::Package = schema::packages::table.find(name)
-            .get_result::<models::Package>(&self.conn)?;
-        let likes: i64 = models::Like::belonging_to(&package)
-            .count()
-            .get_result(&self.conn)?;
-        let likes: i32 = likes as i32;
-        let versions: Vec<models::Version> = models::Version::belonging_to(&package)
-            .load::<models::Version>(&self.conn)?;
-        let dependencies: Vec<models::Dependency> = models::Dependency::belonging_to(&versions)
-            .load::<models::Dependency>(&self.conn)?;
-        let contents: Vec<models::ContentNode> = models::ContentNode::belonging_to(&versions)
-            .load::<models::ContentNode>(&self.conn)?;
-        let maintainers: Vec<String> = schema::users::table
-            .inner_join(schema::maintainers::table.on(
-                schema::users::id.eq(schema::maintainers::user).and(
-                    schema::maintainers::package.eq(name)
-                )
-            ))
-            .select(schema::users::username)
-            .load::<String>(&self.conn)?;
-        let descriptions: Vec<models::Description> = models::Description::belonging_to(&package)
-            .load::<models::Description>(&self.conn)?;
-        let version_texts: Vec<models::VersionText> = models::VersionText::belonging_to(&versions)
-            .load::<models::VersionText>(&self.conn)?;
-        let dep_descriptions: Vec<models::DependencyDescription> =
-            models::DependencyDescription::belonging_to(&dependencies)
-                .load::<models::DependencyDescription>(&self.conn)?;
-
-        // whoever's reading this, I wish you to have fun deciphering that
-        let version_models: Vec<version::Full> = {
-            let grouped_dep_descriptions = dep_descriptions.grouped_by(&dependencies);
-            let grouped_dependencies = dependencies
-                .into_iter()
-                .zip(grouped_dep_descriptions)
-                .grouped_by(&versions);
-            let grouped_contents = contents.grouped_by(&versions);
-            let grouped_texts = version_texts.grouped_by(&versions);
+        let _timer = metrics::db_message("GetPackage");
 
-            versions
-                .into_iter()
-                .zip(grouped_dependencies
-                    .into_iter()
-                    .zip(grouped_contents
-                        .into_iter()
-                        .zip(grouped_texts)))
-                .map(|x| (x.0, (x.1).0, ((x.1).1).0, ((x.1).1).1))
-                .into_iter()
-                .map(|(ver, version_deps, version_contents, texts)| {
-                    let len = texts.len();
-                    let mut changes: Vec<Localized> = Vec::with_capacity(len);
-                    let mut readmes: Vec<Localized> = Vec::with_capacity(len);
-
-                    for x in texts {
-                        changes.push(Localized {
-                            language: x.language,
-                            text: x.changes,
-                        });
+        if let Some(package) = self.cache.package(&msg.0) {
+            return Ok(package);
+        }
 
-                        readmes.push(Localized {
-                            language: x.language,
-                            text: x.readme,
-                        });
-                    };
-
-                    let dependencies = version_deps.into_iter().map(|(dep, desc)| dependency::Full {
-                        package: dep.package,
-                        spec: dep.spec,
-                        dep_type: dep.dep_type,
-                        description: match dep.dep_type {
-                            models::types::DependencyType::Optional => {
-                                Some(desc.into_iter().map(|x| Localized {
-                                    language: x.language,
-                                    text: x.description,
-                                }).collect())
-                            }
-                            _ => None,
-                        },
-                    }).collect();
-
-                    version::Full {
-                        version: ver.version,
-                        changes,
-                        readme: readmes,
-                        // TODO
-                        url: "".to_string(),
-                        dependencies,
-                        contents: version_contents.into_iter().map(|x| ContentNode {
-                            node_type: x.node_type,
-                            path: x.path,
-                        }).collect(),
-                        created: ver.created,
-                    }
-                }).collect()
+        let generation = self.cache.generation();
+        let conn = self.conn()?;
+        let package = load_package(&conn, &msg.0)?;
+
+        self.cache.put_package(generation, &package);
+
+        Ok(package)
+    }
+}
+
+/// Loads a package in two queries: the package row with its aggregates, and
+/// its versions with everything they contain as JSON.
+///
+/// `name` is matched case-insensitively against current and previous names;
+/// the result has the canonical one. The queries live in `sql/`, where the
+/// get_package bench reads them too.
+fn load_package(conn: &PgConnection, name: &str) -> Result<package::Full, Error> {
+    let package: models::PackageRow = diesel::sql_query(include_str!("sql/package.sql"))
+        .bind::<Text, _>(name)
+        .get_result(conn)?;
+
+    let versions: Vec<models::VersionRow> = diesel::sql_query(include_str!("sql/versions.sql"))
+        .bind::<Text, _>(&package.name)
+        .load(conn)?;
+
+    let versions = versions.into_iter()
+        .map(load_version)
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(package::Full {
+        name: package.name,
+        description: serde_json::from_str(&package.descriptions)?,
+        website: package.website,
+        license: package.license,
+        authors: package.authors,
+        maintainers: package.maintainers.into_iter().map(|username| user::Short {
+            username,
+        }).collect(),
+        versions,
+        downloads: package.downloads,
+        likes: package.likes,
+        created: package.created,
+        updated: package.updated,
+    })
+}
+
+fn load_version(row: models::VersionRow) -> Result<version::Full, Error> {
+    let texts: Vec<models::VersionTextJson> = serde_json::from_str(&row.texts)?;
+    let dependencies: Vec<models::DependencyJson> = serde_json::from_str(&row.dependencies)?;
+    let contents: Vec<models::ContentNodeJson> = serde_json::from_str(&row.contents)?;
+
+    let len = texts.len();
+    let mut changes = Vec::with_capacity(len);
+    let mut readmes = Vec::with_capacity(len);
+    let mut rendered = version::Rendered {
+        changes: Vec::with_capacity(len),
+        readme: Vec::with_capacity(len),
+    };
+
+    for text in texts {
+        // texts published before rendering was introduced have no cached HTML
+        let changes_html = match text.changes_html {
+            Some(html) => html,
+            None => markdown::render(&text.changes),
+        };
+        let readme_html = match text.readme_html {
+            Some(html) => html,
+            None => markdown::render(&text.readme),
         };
 
-        Ok(package::Full {
-            name: package.name,
-            description: descriptions.into_iter().map(|x| Localized {
-                language: x.language,
-                text: x.description,
-            }).collect(),
-            website: package.website,
-            license: package.license,
-            authors: package.authors,
-            maintainers: maintainers.into_iter().map(|username| user::Short {
-                username,
-            }).collect(),
-            versions: version_models,
-            downloads: package.downloads,
-            likes,
-            created: package.created,
-            updated: package.updated,
-        })
+        rendered.changes.push(Localized {
+            language: text.language.clone(),
+            text: changes_html,
+        });
+
+        rendered.readme.push(Localized {
+            language: text.language.clone(),
+            text: readme_html,
+        });
+
+        changes.push(Localized {
+            language: text.language.clone(),
+            text: text.changes,
+        });
+
+        readmes.push(Localized {
+            language: text.language,
+            text: text.readme,
+        });
     }
+
+    let dependencies = dependencies.into_iter().map(|dep| {
+        let dep_type: models::types::DependencyType = dep.dep_type.parse()?;
+
+        Ok(dependency::Full {
+            package: dep.package,
+            spec: dep.spec,
+            dep_type,
+            description: match dep_type {
+                models::types::DependencyType::Optional => Some(dep.descriptions),
+                _ => None,
+            },
+        })
+    }).collect::<Result<Vec<_>, Error>>()?;
+
+    let contents = contents.into_iter().map(|node| {
+        Ok(ContentNode {
+            node_type: node.node_type.parse()?,
+            path: node.path,
+        })
+    }).collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(version::Full {
+        version: row.version,
+        changes,
+        readme: readmes,
+        rendered: Some(rendered),
+        // TODO
+        url: "".to_string(),
+        dependencies,
+        contents,
+        created: row.created,
+    })
 }
 
 pub struct GetUser(pub String);
@@ -151,11 +215,14 @@ impl Handler<GetUser> for DbExecutor {
     type Result = Result<user::Full, Error>;
 
     fn handle(&mut self, msg: GetUser, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("GetUser");
+        let conn = self.conn()?;
+
         let username = msg.0;
 
         let user = schema::users::table
             .filter(lower(schema::users::username).eq(username.to_lowercase()))
-            .get_result::<models::User>(&self.conn)?;
+            .get_result::<models::User>(&conn)?;
 
         Ok(user::Full {
             username: user.username,
@@ -171,120 +238,294 @@ pub struct GetPackages {
 }
 
 impl Message for GetPackages {
-    type Result = Result<Vec<package::Short>, Error>;
+    type Result = Result<(Vec<package::Short>, NaiveDateTime), Error>;
 }
 
 impl Handler<GetPackages> for DbExecutor {
-    type Result = Result<Vec<package::Short>, Error>;
+    type Result = Result<(Vec<package::Short>, NaiveDateTime), Error>;
 
     fn handle(&mut self, msg: GetPackages, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("GetPackages");
+
+        if let Some(page) = self.cache.page(msg.page, msg.limit) {
+            return Ok(page);
+        }
+
+        let generation = self.cache.generation();
+        let conn = self.conn()?;
+
+        let updated = catalog_updated(&conn)?;
         let offset = (msg.page - 1) * msg.limit;
 
         let packages: Vec<models::Package> = schema::packages::table
             .offset(offset.into())
             .limit(msg.limit.into())
-            .order_by(schema::packages::created.desc())
-            .load(&self.conn)?;
-        let descriptions: Vec<models::Description> = models::Description::belonging_to(&packages)
-            .load(&self.conn)?;
-
-        let maintainer_models: Vec<models::Maintainer> = models::Maintainer::belonging_to(&packages)
-            .load(&self.conn)?;
-        let referenced_users: Vec<models::User> = schema::users::table
-            .filter(
-                schema::users::id.eq(
-                    diesel::dsl::any(maintainer_models
-                        .iter()
-                        .map(|x| *x.id().0)
-                        .collect::<Vec<_>>()
+            .order_by((schema::packages::created.desc(), schema::packages::name.desc()))
+            .load(&conn)?;
+        let packages = load_short(&conn, packages)?;
+
+        self.cache.put_page(generation, msg.page, msg.limit, &packages, updated);
+
+        Ok((packages, updated))
+    }
+}
+
+/// Returns when the package list last changed. Read it before the list: a
+/// list newer than the time only costs clients a full response.
+fn catalog_updated(conn: &PgConnection) -> Result<NaiveDateTime, Error> {
+    Ok(schema::catalog::table
+        .select(schema::catalog::updated)
+        .get_result(conn)?)
+}
+
+/// Lists packages after `cursor`, or from the newest one if it's `None`.
+/// Returns the cursor of the next page and when the list last changed.
+pub struct GetPackagesAfter {
+    pub cursor: Option<api::Cursor>,
+    pub limit: u32,
+}
+
+impl Message for GetPackagesAfter {
+    type Result = Result<(Vec<package::Short>, Option<api::Cursor>, NaiveDateTime), Error>;
+}
+
+impl Handler<GetPackagesAfter> for DbExecutor {
+    type Result = Result<(Vec<package::Short>, Option<api::Cursor>, NaiveDateTime), Error>;
+
+    fn handle(&mut self, msg: GetPackagesAfter, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("GetPackagesAfter");
+
+        let conn = self.conn()?;
+        let updated = catalog_updated(&conn)?;
+
+        let mut query = schema::packages::table
+            .order_by((schema::packages::created.desc(), schema::packages::name.desc()))
+            // one extra row tells whether there is a next page
+            .limit(i64::from(msg.limit) + 1)
+            .into_boxed();
+
+        if let Some(cursor) = msg.cursor {
+            query = query.filter(
+                schema::packages::created.lt(cursor.created).or(
+                    schema::packages::created.eq(cursor.created).and(
+                        schema::packages::name.lt(cursor.name)
                     )
                 )
-            ).load::<models::User>(&self.conn)?;
-        let referenced_users: HashMap<i32, String> = referenced_users
-            .into_iter()
-            .map(|x| (x.id, x.username))
-            .collect();
-        let maintainers = maintainer_models
-            .grouped_by(&packages)
-            .into_iter()
-            .map(|x| {
-                x.into_iter()
-                    .map(|model| referenced_users.get(&model.user).unwrap().clone())
-                    .collect::<Vec<_>>()
-            });
+            );
+        }
 
-        let likes: Vec<models::GroupedLike> = schema::likes::table
-                .select((schema::likes::package, diesel::dsl::sql::<BigInt>("count(*) as likes")))
-                .filter(schema::likes::package.eq(diesel::dsl::any(
-                    packages.iter().map(|x| &x.name).collect::<Vec<_>>()
-                )))
-                .group_by(schema::likes::package)
-                .load(&self.conn)?;
-        let likes: HashMap<String, i32> = likes
-            .into_iter()
-            .map(|x| (x.package, x.likes as i32))
-            .collect();
-        let versions: Vec<models::Version> = models::Version::belonging_to(&packages)
-            .load(&self.conn)?;
-        let dependencies: Vec<models::Dependency> = models::Dependency::belonging_to(&versions)
-            .load(&self.conn)?;
-
-        let grouped_dependencies = dependencies.into_iter().grouped_by(&versions);
-        let grouped_versions = versions
-            .into_iter()
-            .zip(grouped_dependencies)
-            .grouped_by(&packages)
-            .into_iter()
-            .zip(descriptions.into_iter().grouped_by(&packages));
-
-        Ok(packages
-            .into_iter()
-            .map(|x| {
-                let likes_num = *likes.get(&x.name).unwrap();
-                (x, likes_num)
+        let mut packages: Vec<models::Package> = query.load(&conn)?;
+
+        let next = if packages.len() > msg.limit as usize {
+            packages.truncate(msg.limit as usize);
+            packages.last().map(|x| api::Cursor {
+                created: x.created,
+                name: x.name.clone(),
             })
-            .zip(
-                maintainers.zip(
-                    grouped_versions
+        } else {
+            None
+        };
+
+        Ok((load_short(&conn, packages)?, next, updated))
+    }
+}
+
+/// Builds listing entries for `packages`, keeping their order.
+fn load_short(conn: &PgConnection, packages: Vec<models::Package>)
+    -> Result<Vec<package::Short>, Error>
+{
+    let descriptions: Vec<models::Description> = models::Description::belonging_to(&packages)
+        .load(conn)?;
+
+    let maintainer_models: Vec<models::Maintainer> = models::Maintainer::belonging_to(&packages)
+        .load(conn)?;
+    let referenced_users: Vec<models::User> = schema::users::table
+        .filter(
+            schema::users::id.eq(
+                diesel::dsl::any(maintainer_models
+                    .iter()
+                    .map(|x| *x.id().0)
+                    .collect::<Vec<_>>()
                 )
             )
-            .map(|((package, likes), (maintainers, (versions, descriptions)))| {
-                package::Short {
-                    name: package.name,
-                    description: descriptions.into_iter().map(|x| Localized {
-                        language: x.language,
-                        text: x.description,
-                    }).collect(),
-                    maintainers: maintainers.into_iter().map(|x| user::Short {
-                        username: x,
-                    }).collect(),
-                    versions: versions.into_iter().map(|(version, dependencies)| version::Short {
-                        version: version.version,
-                        // TODO
-                        url: "".to_string(),
-                        dependencies: dependencies.into_iter().map(|x| dependency::Short {
-                            package: x.package,
-                            spec: x.spec,
-                        }).collect(),
+        ).load::<models::User>(conn)?;
+    let referenced_users: HashMap<i32, String> = referenced_users
+        .into_iter()
+        .map(|x| (x.id, x.username))
+        .collect();
+    let maintainers = maintainer_models
+        .grouped_by(&packages)
+        .into_iter()
+        .map(|x| {
+            x.into_iter()
+                .map(|model| referenced_users.get(&model.user).unwrap().clone())
+                .collect::<Vec<_>>()
+        });
+
+    let versions: Vec<models::Version> = models::Version::belonging_to(&packages)
+        .load(conn)?;
+    let dependencies: Vec<models::Dependency> = models::Dependency::belonging_to(&versions)
+        .load(conn)?;
+
+    let grouped_dependencies = dependencies.into_iter().grouped_by(&versions);
+    let grouped_versions = versions
+        .into_iter()
+        .zip(grouped_dependencies)
+        .grouped_by(&packages)
+        .into_iter()
+        .zip(descriptions.into_iter().grouped_by(&packages));
+
+    Ok(packages
+        .into_iter()
+        .zip(
+            maintainers.zip(
+                grouped_versions
+            )
+        )
+        .map(|(package, (maintainers, (versions, descriptions)))| {
+            package::Short {
+                name: package.name,
+                description: descriptions.into_iter().map(|x| Localized {
+                    language: x.language,
+                    text: x.description,
+                }).collect(),
+                maintainers: maintainers.into_iter().map(|x| user::Short {
+                    username: x,
+                }).collect(),
+                versions: versions.into_iter().map(|(version, dependencies)| version::Short {
+                    version: version.version,
+                    // TODO
+                    url: "".to_string(),
+                    dependencies: dependencies.into_iter().map(|x| dependency::Short {
+                        package: x.package,
+                        spec: x.spec,
                     }).collect(),
-                    downloads: package.downloads,
-                    likes,
-                }
-            }).collect())
+                }).collect(),
+                downloads: package.downloads,
+                likes: package.likes,
+                updated: package.updated,
+            }
+        }).collect())
+}
+
+
+/// Finds the canonical name of a package by its current or a previous name,
+/// in any case.
+fn resolve_name(conn: &PgConnection, name: &str) -> QueryResult<Option<String>> {
+    let found = schema::packages::table
+        .select(schema::packages::name)
+        .filter(lower(schema::packages::name).eq(name.to_lowercase()))
+        .first(conn)
+        .optional()?;
+
+    if found.is_some() {
+        return Ok(found);
+    }
+
+    schema::package_aliases::table
+        .select(schema::package_aliases::package)
+        .filter(lower(schema::package_aliases::name).eq(name.to_lowercase()))
+        .first(conn)
+        .optional()
+}
+
+/// Rejects names that differ from an existing name or alias only in case,
+/// or that are similar enough to be mistaken for another package. `owner`,
+/// when renaming, is the package allowed to hold the name already.
+fn check_name_available(conn: &PgConnection, name: &str, owner: Option<&str>,
+                        config: &NamesGroup) -> Result<(), Error> {
+    match resolve_name(conn, name)? {
+        // a package may always take back one of its own names
+        Some(ref existing) if Some(existing.as_str()) == owner => return Ok(()),
+        Some(existing) => {
+            return Err(ValidationError::NameTaken {
+                name: name.to_string(),
+                existing,
+            }.into());
+        }
+        None => {}
+    }
+
+    if config.similarity == 0 {
+        return Ok(());
+    }
+
+    // Only names whose skeleton length is within the distance can be
+    // similar. This still scans every package name, which is fine for
+    // creates and renames.
+    let names: Vec<models::PackageName> = diesel::sql_query(
+        "SELECT name FROM packages \
+            WHERE abs(char_length(translate(name, '-_', '')) - $1) <= $2")
+        .bind::<Integer, _>(validate::name_skeleton(name).len() as i32)
+        .bind::<Integer, _>(config.similarity as i32)
+        .load(conn)?;
+    let names = names.iter()
+        .map(|x| x.name.as_str())
+        .filter(|&x| Some(x) != owner);
+
+    if let Some(existing) = validate::similar_name(name, names, config.similarity) {
+        return Err(ValidationError::SimilarName {
+            name: name.to_string(),
+            existing: existing.to_string(),
+        }.into());
     }
+
+    Ok(())
 }
 
+/// Creates a package, returning file conflicts of its latest version with
+/// other packages.
 pub struct CreatePackage(pub package::Full);
 
 impl Message for CreatePackage {
-    type Result = Result<(), Error>;
+    type Result = Result<Vec<conflict::Conflict>, Error>;
 }
 
 impl Handler<CreatePackage> for DbExecutor {
-    type Result = Result<(), Error>;
+    type Result = Result<Vec<conflict::Conflict>, Error>;
+
+    fn handle(&mut self, mut msg: CreatePackage, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("CreatePackage");
+
+        for version in msg.0.versions.iter_mut() {
+            let nodes = mem::take(&mut version.contents);
+
+            version.contents = validate::contents(nodes).map_err(|errors| {
+                ValidationError::InvalidContents {
+                    version: version.version.clone(),
+                    errors: ContentErrors(errors),
+                }
+            })?;
+        }
 
-    fn handle(&mut self, msg: CreatePackage, _: &mut Self::Context) -> Self::Result {
-        self.conn.transaction::<(), Error, _>(|| {
+        validate::package_name(&msg.0.name, &self.names)?;
+
+        // versions are inserted oldest first, so the last one is the latest
+        // both here and for conflict checks of later packages
+        msg.0.versions.sort_by(|a, b| validate::compare_versions(&a.version, &b.version));
+
+        let conn = self.conn()?;
+        let name = msg.0.name.clone();
+
+        check_name_available(&conn, &name, None, &self.names)?;
+
+        // dependencies may name a package by a previous name or in another case
+        let mut dependency_names: HashMap<String, String> = HashMap::new();
+
+        for dep in msg.0.versions.iter().flat_map(|x| x.dependencies.iter()) {
+            if !dependency_names.contains_key(&dep.package) {
+                let canonical = match resolve_name(&conn, &dep.package)? {
+                    Some(canonical) => canonical,
+                    None if dep.package.eq_ignore_ascii_case(&name) => name.clone(),
+                    None => return Err(ValidationError::UnknownDependency {
+                        package: dep.package.clone(),
+                    }.into()),
+                };
+                dependency_names.insert(dep.package.clone(), canonical);
+            }
+        }
+
+        let result = conn.transaction::<_, Error, _>(|| {
             let name = &msg.0.name;
 
             insert_into(schema::packages::table).values(&models::NewPackage {
@@ -292,7 +533,7 @@ impl Handler<CreatePackage> for DbExecutor {
                 website: &msg.0.website,
                 license: &msg.0.license,
                 authors: &msg.0.authors,
-            }).execute(&self.conn)?;
+            }).execute(&conn)?;
 
             let versions: HashMap<String, i32> = insert_into(schema::versions::table)
                 .values(&msg.0.versions.iter().map(|x| models::NewVersion {
@@ -300,7 +541,7 @@ impl Handler<CreatePackage> for DbExecutor {
                     version: &x.version,
                 }).collect::<Vec<_>>())
                 .returning((schema::versions::version, schema::versions::id))
-                .get_results(&self.conn)?
+                .get_results(&conn)?
                 .into_iter()
                 .collect();
 
@@ -316,7 +557,7 @@ impl Handler<CreatePackage> for DbExecutor {
 
                     for dep in version.dependencies.iter() {
                         values.push(models::NewDependency {
-                            package: &name,
+                            package: &dependency_names[&dep.package],
                             version: version_id,
                             spec: &dep.spec,
                             dep_type: dep.dep_type,
@@ -328,7 +569,7 @@ impl Handler<CreatePackage> for DbExecutor {
                     .values(&values)
                     .returning(((schema::dependencies::version, schema::dependencies::package),
                                 schema::dependencies::id))
-                    .get_results(&self.conn)?
+                    .get_results(&conn)?
             };
 
             {
@@ -341,13 +582,16 @@ impl Handler<CreatePackage> for DbExecutor {
                         if let Some(desc) = dep.description.as_ref() {
                             let dep_id = dependencies
                                 .iter()
-                                .find(|x| (x.0).0 == version_id && &(x.0).1 == &dep.package)
+                                .find(|x| {
+                                    (x.0).0 == version_id
+                                        && (x.0).1 == dependency_names[&dep.package]
+                                })
                                 .unwrap().1;
 
                             for text in desc.iter() {
                                 values.push(models::NewDependencyDescription {
                                     dependency: dep_id,
-                                    language: text.language,
+                                    language: &text.language,
                                     description: &text.text,
                                 });
                             }
@@ -357,7 +601,7 @@ impl Handler<CreatePackage> for DbExecutor {
 
                 insert_into(schema::dependency_descriptions::table)
                     .values(&values)
-                    .execute(&self.conn)?;
+                    .execute(&conn)?;
             }
 
             {
@@ -377,7 +621,7 @@ impl Handler<CreatePackage> for DbExecutor {
 
                 insert_into(schema::contents::table)
                     .values(&values)
-                    .execute(&self.conn)?;
+                    .execute(&conn)?;
             }
 
             {
@@ -386,10 +630,10 @@ impl Handler<CreatePackage> for DbExecutor {
                 for version in msg.0.versions.iter() {
                     let version_id = get_version_id(version);
 
-                    let mut changes: HashMap<models::types::Language, &Localized> = HashMap::new();
+                    let mut changes: HashMap<&models::types::Language, &Localized> = HashMap::new();
 
                     for text in version.changes.iter() {
-                        changes.insert(text.language, &text);
+                        changes.insert(&text.language, text);
                     }
 
                     for readme in version.readme.iter() {
@@ -397,32 +641,34 @@ impl Handler<CreatePackage> for DbExecutor {
 
                         values.push(models::NewVersionText {
                             version: version_id,
-                            language: readme.language,
+                            language: &readme.language,
                             changes: &changes_text.text,
                             readme: &readme.text,
+                            changes_html: Some(markdown::render(&changes_text.text)),
+                            readme_html: Some(markdown::render(&readme.text)),
                         });
                     }
                 }
 
                 insert_into(schema::version_texts::table)
                     .values(&values)
-                    .execute(&self.conn)?;
+                    .execute(&conn)?;
             }
 
             insert_into(schema::descriptions::table).values(&msg.0.description.iter().map(|desc| {
                 models::NewDescription {
                     package: &name,
-                    language: desc.language,
+                    language: &desc.language,
                     description: &desc.text,
                 }
-            }).collect::<Vec<_>>()).execute(&self.conn)?;
+            }).collect::<Vec<_>>()).execute(&conn)?;
 
             let users: HashMap<String, i32> = schema::users::table
                 .select((schema::users::username, schema::users::id))
                 .filter(lower(schema::users::username).eq(diesel::dsl::any(
                     &msg.0.maintainers.iter().map(|x| x.username.to_lowercase()).collect::<Vec<_>>()
                 )))
-                .get_results::<(String, i32)>(&self.conn)?
+                .get_results::<(String, i32)>(&conn)?
                 .into_iter()
                 .map(|x| (x.0.to_lowercase(), x.1))
                 .collect();
@@ -432,10 +678,20 @@ impl Handler<CreatePackage> for DbExecutor {
                     user: *users.get(&x.username.to_lowercase()).unwrap(),
                     package: &name,
                 }
-            }).collect::<Vec<_>>()).execute(&self.conn)?;
+            }).collect::<Vec<_>>()).execute(&conn)?;
 
-            Ok(())
-        })
+            match msg.0.versions.last() {
+                Some(latest) => {
+                    let version_id = get_version_id(latest);
+                    find_conflicts(&conn, name, version_id)
+                }
+                None => Ok(Vec::new()),
+            }
+        });
+
+        self.cache.invalidate(&name);
+
+        result
     }
 }
 
@@ -454,6 +710,9 @@ impl Handler<CreateUser> for DbExecutor {
     type Result = Result<i32, Error>;
 
     fn handle(&mut self, msg: CreateUser, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("CreateUser");
+        let conn = self.conn()?;
+
         Ok(insert_into(schema::users::table)
             .values(&models::NewUser {
                 username: &msg.username,
@@ -462,6 +721,653 @@ impl Handler<CreateUser> for DbExecutor {
                 group: msg.group,
             })
             .returning(schema::users::id)
-            .get_result(&self.conn)?)
+            .get_result(&conn)?)
+    }
+}
+
+/// Finds files of the given version that are also installed by the latest
+/// version of some other package.
+fn find_conflicts(conn: &PgConnection, package: &str, version_id: i32)
+    -> Result<Vec<conflict::Conflict>, Error>
+{
+    let nodes: Vec<models::ConflictingNode> = diesel::sql_query(
+        "WITH paths AS (SELECT path FROM contents WHERE version = $2 AND type = 'file'), \
+            latest AS (SELECT DISTINCT ON (package) id FROM versions \
+                WHERE package IN (SELECT v.package FROM contents c \
+                    INNER JOIN versions v ON v.id = c.version \
+                    WHERE c.type = 'file' AND c.path IN (SELECT path FROM paths) \
+                    AND v.package <> $1) \
+                ORDER BY package, created DESC, id DESC) \
+        SELECT v.package, v.version, c.path FROM contents c \
+            INNER JOIN versions v ON v.id = c.version \
+            WHERE c.type = 'file' AND v.id IN (SELECT id FROM latest) \
+            AND c.path IN (SELECT path FROM paths) \
+            ORDER BY v.package, c.path"
+    )
+        .bind::<Text, _>(package)
+        .bind::<Integer, _>(version_id)
+        .load(conn)?;
+
+    let mut conflicts: Vec<conflict::Conflict> = Vec::new();
+
+    for node in nodes {
+        if let Some(last) = conflicts.last_mut() {
+            if last.package == node.package {
+                last.paths.push(node.path);
+                continue;
+            }
+        }
+
+        conflicts.push(conflict::Conflict {
+            package: node.package,
+            version: node.version,
+            paths: vec![node.path],
+        });
+    }
+
+    Ok(conflicts)
+}
+
+pub struct GetConflicts {
+    pub package: String,
+    pub version: String,
+}
+
+impl Message for GetConflicts {
+    type Result = Result<Vec<conflict::Conflict>, Error>;
+}
+
+impl Handler<GetConflicts> for DbExecutor {
+    type Result = Result<Vec<conflict::Conflict>, Error>;
+
+    fn handle(&mut self, msg: GetConflicts, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("GetConflicts");
+        let conn = self.conn()?;
+
+        let name = resolve_name(&conn, &msg.package)?
+            .ok_or(diesel::result::Error::NotFound)?;
+
+        let version_id: i32 = schema::versions::table
+            .select(schema::versions::id)
+            .filter(schema::versions::package.eq(&name))
+            .filter(schema::versions::version.eq(&msg.version))
+            .get_result(&conn)?;
+
+        find_conflicts(&conn, &name, version_id)
     }
-}
\ No newline at end of file
+}
+
+pub struct DeletePackage(pub String);
+
+impl Message for DeletePackage {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<DeletePackage> for DbExecutor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: DeletePackage, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("DeletePackage");
+        let conn = self.conn()?;
+
+        let name = resolve_name(&conn, &msg.0)?
+            .ok_or(diesel::result::Error::NotFound)?;
+
+        let dependents: Vec<String> = schema::dependencies::table
+            .inner_join(schema::versions::table)
+            .select(schema::versions::package)
+            .filter(schema::dependencies::package.eq(&name))
+            .filter(schema::versions::package.ne(&name))
+            .distinct()
+            .order(schema::versions::package)
+            .load(&conn)?;
+
+        if !dependents.is_empty() {
+            return Err(ValidationError::HasDependents {
+                name,
+                dependents: dependents.join(", "),
+            }.into());
+        }
+
+        conn.transaction::<_, Error, _>(|| {
+            // versions go first, taking their dependencies with them, so that
+            // a dependency of the package on itself doesn't block deleting it
+            diesel::delete(schema::versions::table.filter(schema::versions::package.eq(&name)))
+                .execute(&conn)?;
+
+            let deleted = diesel::delete(schema::packages::table.find(&name))
+                .execute(&conn)?;
+
+            if deleted == 0 {
+                return Err(diesel::result::Error::NotFound.into());
+            }
+
+            Ok(())
+        })?;
+
+        self.cache.clear();
+
+        Ok(())
+    }
+}
+
+/// Renames a package. The old name is kept as an alias, so lookups and new
+/// dependencies still find the package under it.
+pub struct RenamePackage {
+    pub name: String,
+    pub new_name: String,
+}
+
+impl Message for RenamePackage {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<RenamePackage> for DbExecutor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: RenamePackage, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("RenamePackage");
+
+        validate::package_name(&msg.new_name, &self.names)?;
+
+        let conn = self.conn()?;
+
+        let name = resolve_name(&conn, &msg.name)?
+            .ok_or(diesel::result::Error::NotFound)?;
+
+        check_name_available(&conn, &msg.new_name, Some(&name), &self.names)?;
+
+        conn.transaction::<_, Error, _>(|| {
+            // the package may be taking back one of its previous names
+            diesel::delete(schema::package_aliases::table
+                .filter(lower(schema::package_aliases::name).eq(msg.new_name.to_lowercase())))
+                .execute(&conn)?;
+
+            // references are updated by ON UPDATE CASCADE
+            diesel::update(schema::packages::table.find(&name))
+                .set((
+                    schema::packages::name.eq(&msg.new_name),
+                    schema::packages::updated.eq(diesel::dsl::now),
+                ))
+                .execute(&conn)?;
+
+            // lookups are case-insensitive anyway
+            if name.to_lowercase() != msg.new_name.to_lowercase() {
+                insert_into(schema::package_aliases::table)
+                    .values(&models::NewPackageAlias {
+                        name: &name,
+                        package: &msg.new_name,
+                    })
+                    .execute(&conn)?;
+            }
+
+            Ok(())
+        })?;
+
+        // dependencies on the package are renamed with it
+        self.cache.clear();
+
+        Ok(())
+    }
+}
+
+/// A user who proved who they are with their password.
+#[derive(Clone, Debug)]
+pub struct Identity {
+    pub id: i32,
+    pub username: String,
+    pub group: models::types::UserGroup,
+}
+
+/// Checks a password, returning `None` if the user doesn't exist or the
+/// password is wrong.
+pub struct Authenticate {
+    pub username: String,
+    pub password: String,
+}
+
+impl Message for Authenticate {
+    type Result = Result<Option<Identity>, Error>;
+}
+
+impl Handler<Authenticate> for DbExecutor {
+    type Result = Result<Option<Identity>, Error>;
+
+    fn handle(&mut self, msg: Authenticate, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("Authenticate");
+        let conn = self.conn()?;
+
+        let user = schema::users::table
+            .filter(lower(schema::users::username).eq(msg.username.to_lowercase()))
+            .get_result::<models::User>(&conn)
+            .optional()?;
+
+        let user = match user {
+            Some(user) => user,
+            None => {
+                // as slow as a wrong password, so usernames can't be probed
+                password::verify(&msg.password, &[0; 64], &[0; 64]);
+                return Ok(None);
+            }
+        };
+
+        if !password::verify(&msg.password, &user.salt, &user.password) {
+            return Ok(None);
+        }
+
+        Ok(Some(Identity {
+            id: user.id,
+            username: user.username,
+            group: user.group,
+        }))
+    }
+}
+
+/// Locks a package found by any of its names against concurrent changes of
+/// its maintainers, returning its canonical name.
+fn lock_package(conn: &PgConnection, name: &str) -> Result<String, Error> {
+    let name = resolve_name(conn, name)?
+        .ok_or(diesel::result::Error::NotFound)?;
+
+    Ok(schema::packages::table
+        .find(&name)
+        .select(schema::packages::name)
+        .for_update()
+        .get_result(conn)?)
+}
+
+fn find_user(conn: &PgConnection, username: &str) -> QueryResult<models::User> {
+    schema::users::table
+        .filter(lower(schema::users::username).eq(username.to_lowercase()))
+        .get_result(conn)
+}
+
+fn is_maintainer(conn: &PgConnection, package: &str, user: i32) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(schema::maintainers::table
+        .filter(schema::maintainers::package.eq(package))
+        .filter(schema::maintainers::user.eq(user))))
+        .get_result(conn)
+}
+
+fn is_invited(conn: &PgConnection, package: &str, user: i32) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(schema::maintainer_invitations::table
+        .filter(schema::maintainer_invitations::package.eq(package))
+        .filter(schema::maintainer_invitations::user.eq(user))))
+        .get_result(conn)
+}
+
+/// Maintainers of a package and admins may change its maintainers.
+fn check_manager(conn: &PgConnection, package: &str, by: &Identity) -> Result<(), Error> {
+    if by.group == models::types::UserGroup::Admin || is_maintainer(conn, package, by.id)? {
+        return Ok(());
+    }
+
+    Err(MaintainerError::NotMaintainer { package: package.to_string() }.into())
+}
+
+fn list_maintainers(conn: &PgConnection, package: &str) -> QueryResult<maintainer::List> {
+    let maintainers: Vec<String> = schema::users::table
+        .inner_join(schema::maintainers::table.on(
+            schema::users::id.eq(schema::maintainers::user)
+        ))
+        .filter(schema::maintainers::package.eq(package))
+        .select(schema::users::username)
+        .order_by(schema::users::username)
+        .load(conn)?;
+    let invited: Vec<String> = schema::users::table
+        .inner_join(schema::maintainer_invitations::table.on(
+            schema::users::id.eq(schema::maintainer_invitations::user)
+        ))
+        .filter(schema::maintainer_invitations::package.eq(package))
+        .select(schema::users::username)
+        .order_by(schema::maintainer_invitations::created)
+        .load(conn)?;
+
+    Ok(maintainer::List {
+        maintainers: maintainers.into_iter().map(|username| user::Short { username }).collect(),
+        invited: invited.into_iter().map(|username| user::Short { username }).collect(),
+    })
+}
+
+pub struct GetMaintainers(pub String);
+
+impl Message for GetMaintainers {
+    type Result = Result<maintainer::List, Error>;
+}
+
+impl Handler<GetMaintainers> for DbExecutor {
+    type Result = Result<maintainer::List, Error>;
+
+    fn handle(&mut self, msg: GetMaintainers, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("GetMaintainers");
+        let conn = self.conn()?;
+
+        let name = resolve_name(&conn, &msg.0)?
+            .ok_or(diesel::result::Error::NotFound)?;
+
+        Ok(list_maintainers(&conn, &name)?)
+    }
+}
+
+/// Invites a user to maintain a package. The invitation has no effect until
+/// the user accepts it.
+pub struct InviteMaintainer {
+    pub package: String,
+    pub username: String,
+    pub by: Identity,
+}
+
+impl Message for InviteMaintainer {
+    type Result = Result<maintainer::List, Error>;
+}
+
+impl Handler<InviteMaintainer> for DbExecutor {
+    type Result = Result<maintainer::List, Error>;
+
+    fn handle(&mut self, msg: InviteMaintainer, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("InviteMaintainer");
+        let conn = self.conn()?;
+
+        conn.transaction::<_, Error, _>(|| {
+            let package = lock_package(&conn, &msg.package)?;
+            check_manager(&conn, &package, &msg.by)?;
+
+            let user = find_user(&conn, &msg.username)?;
+
+            if is_maintainer(&conn, &package, user.id)? {
+                return Err(MaintainerError::AlreadyMaintainer {
+                    username: user.username,
+                    package,
+                }.into());
+            }
+
+            insert_into(schema::maintainer_invitations::table)
+                .values(&models::NewMaintainerInvitation {
+                    package: &package,
+                    user: user.id,
+                    invited_by: msg.by.id,
+                })
+                .on_conflict_do_nothing()
+                .execute(&conn)?;
+
+            Ok(list_maintainers(&conn, &package)?)
+        })
+    }
+}
+
+/// Makes the user a maintainer of a package they were invited to.
+pub struct AcceptInvitation {
+    pub package: String,
+    pub by: Identity,
+}
+
+impl Message for AcceptInvitation {
+    type Result = Result<maintainer::List, Error>;
+}
+
+impl Handler<AcceptInvitation> for DbExecutor {
+    type Result = Result<maintainer::List, Error>;
+
+    fn handle(&mut self, msg: AcceptInvitation, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("AcceptInvitation");
+        let conn = self.conn()?;
+
+        let (package, list) = conn.transaction::<_, Error, _>(|| {
+            let package = lock_package(&conn, &msg.package)?;
+
+            let deleted = diesel::delete(schema::maintainer_invitations::table
+                .filter(schema::maintainer_invitations::package.eq(&package))
+                .filter(schema::maintainer_invitations::user.eq(msg.by.id)))
+                .execute(&conn)?;
+
+            if deleted == 0 {
+                let username = msg.by.username.clone();
+
+                return Err(if is_maintainer(&conn, &package, msg.by.id)? {
+                    MaintainerError::AlreadyMaintainer { username, package }
+                } else {
+                    MaintainerError::NoInvitation { username, package }
+                }.into());
+            }
+
+            insert_into(schema::maintainers::table)
+                .values(&models::NewMaintainer {
+                    user: msg.by.id,
+                    package: &package,
+                })
+                .execute(&conn)?;
+
+            let list = list_maintainers(&conn, &package)?;
+
+            Ok((package, list))
+        })?;
+
+        self.cache.invalidate(&package);
+
+        Ok(list)
+    }
+}
+
+/// Removes a maintainer, or withdraws an invitation. Users may remove
+/// themselves; anyone else needs to be allowed to manage the package. The
+/// last maintainer can't be removed.
+pub struct RemoveMaintainer {
+    pub package: String,
+    pub username: String,
+    pub by: Identity,
+}
+
+impl Message for RemoveMaintainer {
+    type Result = Result<maintainer::List, Error>;
+}
+
+impl Handler<RemoveMaintainer> for DbExecutor {
+    type Result = Result<maintainer::List, Error>;
+
+    fn handle(&mut self, msg: RemoveMaintainer, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("RemoveMaintainer");
+        let conn = self.conn()?;
+
+        let (package, list) = conn.transaction::<_, Error, _>(|| {
+            let package = lock_package(&conn, &msg.package)?;
+            let user = find_user(&conn, &msg.username)?;
+
+            if user.id != msg.by.id {
+                check_manager(&conn, &package, &msg.by)?;
+            }
+
+            if is_maintainer(&conn, &package, user.id)? {
+                let count: i64 = schema::maintainers::table
+                    .filter(schema::maintainers::package.eq(&package))
+                    .count()
+                    .get_result(&conn)?;
+
+                if count <= 1 {
+                    return Err(MaintainerError::LastMaintainer {
+                        username: user.username,
+                        package,
+                    }.into());
+                }
+
+                diesel::delete(schema::maintainers::table
+                    .filter(schema::maintainers::package.eq(&package))
+                    .filter(schema::maintainers::user.eq(user.id)))
+                    .execute(&conn)?;
+            } else if is_invited(&conn, &package, user.id)? {
+                diesel::delete(schema::maintainer_invitations::table
+                    .filter(schema::maintainer_invitations::package.eq(&package))
+                    .filter(schema::maintainer_invitations::user.eq(user.id)))
+                    .execute(&conn)?;
+            } else {
+                return Err(MaintainerError::NotListed {
+                    username: user.username,
+                    package,
+                }.into());
+            }
+
+            let list = list_maintainers(&conn, &package)?;
+
+            Ok((package, list))
+        })?;
+
+        self.cache.invalidate(&package);
+
+        Ok(list)
+    }
+}
+
+/// Recomputes like counters from the `likes` table, returning the number of
+/// packages whose counter was wrong. Triggers keep them in sync, so this is
+/// only needed after manual edits.
+pub struct RecountLikes;
+
+impl Message for RecountLikes {
+    type Result = Result<usize, Error>;
+}
+
+impl Handler<RecountLikes> for DbExecutor {
+    type Result = Result<usize, Error>;
+
+    fn handle(&mut self, _: RecountLikes, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("RecountLikes");
+        let conn = self.conn()?;
+
+        let fixed: Vec<models::PackageName> = diesel::sql_query(
+            "UPDATE packages p SET likes = c.likes \
+                FROM (SELECT p.name, count(l.package)::integer AS likes \
+                    FROM packages p LEFT JOIN likes l ON l.package = p.name \
+                    GROUP BY p.name) c \
+                WHERE p.name = c.name AND p.likes <> c.likes \
+                RETURNING p.name"
+        ).load(&conn)?;
+
+        for package in &fixed {
+            self.cache.invalidate(&package.name);
+        }
+
+        Ok(fixed.len())
+    }
+}
+
+pub struct SetUserGroup {
+    pub username: String,
+    pub group: models::types::UserGroup,
+}
+
+impl Message for SetUserGroup {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<SetUserGroup> for DbExecutor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: SetUserGroup, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("SetUserGroup");
+        let conn = self.conn()?;
+
+        let updated = diesel::update(schema::users::table
+            .filter(lower(schema::users::username).eq(msg.username.to_lowercase())))
+            .set(schema::users::group.eq(msg.group))
+            .execute(&conn)?;
+
+        if updated == 0 {
+            return Err(diesel::result::Error::NotFound.into());
+        }
+
+        Ok(())
+    }
+}
+
+pub struct SetPassword {
+    pub username: String,
+    pub password: [u8; 64],
+    pub salt: [u8; 64],
+}
+
+impl Message for SetPassword {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<SetPassword> for DbExecutor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: SetPassword, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("SetPassword");
+        let conn = self.conn()?;
+
+        let updated = diesel::update(schema::users::table
+            .filter(lower(schema::users::username).eq(msg.username.to_lowercase())))
+            .set((
+                schema::users::password.eq(&msg.password[..]),
+                schema::users::salt.eq(&msg.salt[..]),
+            ))
+            .execute(&conn)?;
+
+        if updated == 0 {
+            return Err(diesel::result::Error::NotFound.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that the executor has a working database connection.
+pub struct Ping;
+
+impl Message for Ping {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<Ping> for DbExecutor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, _: Ping, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("Ping");
+        let conn = self.conn()?;
+
+        diesel::sql_query("SELECT 1").execute(&conn)?;
+        Ok(())
+    }
+}
+
+pub struct GetSchemaVersion;
+
+impl Message for GetSchemaVersion {
+    type Result = Result<Option<String>, Error>;
+}
+
+impl Handler<GetSchemaVersion> for DbExecutor {
+    type Result = Result<Option<String>, Error>;
+
+    fn handle(&mut self, _: GetSchemaVersion, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("GetSchemaVersion");
+        let conn = self.conn()?;
+
+        super::schema_version(&conn)
+    }
+}
+
+pub struct Counts {
+    pub packages: i64,
+    pub users: i64,
+}
+
+pub struct GetCounts;
+
+impl Message for GetCounts {
+    type Result = Result<Counts, Error>;
+}
+
+impl Handler<GetCounts> for DbExecutor {
+    type Result = Result<Counts, Error>;
+
+    fn handle(&mut self, _: GetCounts, _: &mut Self::Context) -> Self::Result {
+        let _timer = metrics::db_message("GetCounts");
+        let conn = self.conn()?;
+
+        Ok(Counts {
+            packages: schema::packages::table.count().get_result(&conn)?,
+            users: schema::users::table.count().get_result(&conn)?,
+        })
+    }
+}
@@ -1,21 +1,271 @@
+pub mod cache;
 pub mod messages;
 pub mod models;
 pub mod schema;
+#[cfg(test)]
+pub mod tests;
 
+use std::io::Write;
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use diesel::pg::PgConnection;
+use diesel::r2d2::{self, ConnectionManager};
+use diesel_migrations::MigrationConnection;
 use failure::Error;
 
-use actix::{SyncContext, Actor};
+use actix::{Addr, Handler, Message, SyncArbiter, SyncContext, Actor, Syn};
+use actix::dev::Request;
+
+use config::config_groups::{CacheGroup, DbGroup, NamesGroup};
+use logging::RequestContext;
+use self::cache::Cache;
+use metrics;
+
+embed_migrations!();
 
 pub fn establish_connection(db: &str) -> Result<PgConnection, Error> {
     Ok(PgConnection::establish(db)?)
 }
 
+/// Applies pending embedded migrations, reporting each one to `out`, and
+/// returns the resulting schema version.
+pub fn run_migrations<W: Write>(conn: &PgConnection, out: &mut W) -> Result<Option<String>, Error> {
+    embedded_migrations::run_with_output(conn, out)?;
+    schema_version(conn)
+}
+
+/// Returns the version of the latest applied migration.
+pub fn schema_version(conn: &PgConnection) -> Result<Option<String>, Error> {
+    Ok(conn.latest_run_migration_version()?)
+}
+
+pub type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
+pub type PooledConnection = r2d2::PooledConnection<ConnectionManager<PgConnection>>;
+
+/// Creates a connection pool without connecting, so the server can start
+/// while the database is unavailable. Broken connections are detected on
+/// checkout and replaced.
+pub fn create_pool(config: &DbGroup) -> Pool {
+    let mut builder = r2d2::Pool::builder()
+        .max_size(config.pool_size.unwrap_or(config.threads as u32))
+        .connection_timeout(Duration::from_millis(config.acquire_timeout))
+        .test_on_check_out(true);
+
+    if let Some(max_lifetime) = config.max_lifetime {
+        builder = builder.max_lifetime(Some(Duration::from_secs(max_lifetime)));
+    }
+
+    if let Some(idle_timeout) = config.idle_timeout {
+        builder = builder.idle_timeout(Some(Duration::from_secs(idle_timeout)));
+    }
+
+    builder.build_unchecked(ConnectionManager::new(config.url.clone()))
+}
+
 pub struct DbExecutor {
-    pub conn: PgConnection
+    pub pool: Pool,
+    pub cache: Arc<Cache>,
+    /// Rules new package names are checked against.
+    pub names: Arc<NamesGroup>,
+}
+
+impl DbExecutor {
+    /// Takes a connection from the pool, waiting at most
+    /// `database.acquire_timeout` milliseconds.
+    pub fn conn(&self) -> Result<PooledConnection, Error> {
+        Ok(self.pool.get()?)
+    }
 }
 
 impl Actor for DbExecutor {
     type Context = SyncContext<Self>;
 }
+
+/// Starts `threads` database executors sharing a connection pool and a
+/// cache of package reads.
+pub fn start(config: &DbGroup, cache: &CacheGroup, names: &NamesGroup, threads: usize)
+    -> Addr<Syn, DbExecutor>
+{
+    let pool = create_pool(config);
+    let names = Arc::new(names.clone());
+    let poll = cache.poll;
+    let enabled = cache.enabled();
+    let cache = Arc::new(Cache::new(cache));
+
+    if enabled && poll > 0 {
+        let pool = pool.clone();
+        let cache = Arc::downgrade(&cache);
+
+        thread::spawn(move || watch_catalog(&pool, &cache, Duration::from_secs(poll)));
+    }
+
+    SyncArbiter::start(threads, move || {
+        DbExecutor {
+            pool: pool.clone(),
+            cache: cache.clone(),
+            names: names.clone(),
+        }
+    })
+}
+
+/// Clears the cache whenever the package list changes, which triggers record
+/// for every process writing to the database. Writes made through `cache`'s
+/// own executors invalidate it right away; this catches the others. Stops
+/// once the executors are gone.
+fn watch_catalog(pool: &Pool, cache: &Weak<Cache>, interval: Duration) {
+    let mut seen = None;
+
+    loop {
+        thread::sleep(interval);
+
+        let cache = match cache.upgrade() {
+            Some(cache) => cache,
+            None => return,
+        };
+
+        let updated = pool.get().map_err(Error::from).and_then(|conn| {
+            Ok(schema::catalog::table
+                .select(schema::catalog::updated)
+                .get_result::<NaiveDateTime>(&conn)?)
+        });
+
+        match updated {
+            // the first check also clears what was cached before it
+            Ok(updated) => if seen != Some(updated) {
+                cache.clear();
+                seen = Some(updated);
+            },
+            Err(e) => warn!("failed to check the package list for changes: {}", e),
+        }
+    }
+}
+
+/// Messages sent to the executors whose envelope has not been dropped yet.
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// Sends a message to the database executors on behalf of the request `ctx`,
+/// if any, accounting for it in the mailbox depth metric.
+pub fn send<M>(db: &Addr<Syn, DbExecutor>, ctx: Option<Arc<RequestContext>>, msg: M)
+    -> Request<Syn, DbExecutor, Traced<M>>
+    where M: Message + Send + 'static,
+          Traced<M>: Message,
+          <Traced<M> as Message>::Result: Send,
+          DbExecutor: Handler<Traced<M>>,
+{
+    db.send(Traced {
+        ctx,
+        msg,
+        pending: Pending::new(),
+    })
+}
+
+/// Waits up to `timeout` for messages already sent to the executors to be
+/// handled or discarded. Returns `false` if some are still pending.
+pub fn drain(timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+
+    while IN_FLIGHT.load(Ordering::SeqCst) > 0 {
+        if Instant::now() >= deadline {
+            return false;
+        }
+
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    true
+}
+
+/// Accounts for a message from the moment it is sent until it is dropped.
+///
+/// The guard lives in the message rather than in the handler: actix drops
+/// messages whose sender has gone away (a timeout or a client disconnect)
+/// without handling them.
+struct Pending {
+    picked_up: bool,
+}
+
+impl Pending {
+    fn new() -> Pending {
+        IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+        metrics::DB_IN_FLIGHT.inc();
+        metrics::DB_MAILBOX_DEPTH.inc();
+
+        Pending { picked_up: false }
+    }
+
+    /// Marks the message as taken out of the mailbox by an executor.
+    fn pick_up(&mut self) {
+        if !self.picked_up {
+            self.picked_up = true;
+            metrics::DB_MAILBOX_DEPTH.dec();
+        }
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.pick_up();
+        metrics::DB_IN_FLIGHT.dec();
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A message handled on behalf of an HTTP request: records logged while it is
+/// handled carry the request id, and the time spent counts towards the
+/// request's database time.
+pub struct Traced<M> {
+    ctx: Option<Arc<RequestContext>>,
+    msg: M,
+    pending: Pending,
+}
+
+impl<M, T> Message for Traced<M>
+    where M: Message<Result = Result<T, Error>>,
+          T: 'static,
+{
+    type Result = Result<T, Error>;
+}
+
+/// Handles a `Traced` message by running its inner message in the scope of
+/// the request it was sent for.
+pub fn handle_traced<M, T>(db: &mut DbExecutor, traced: Traced<M>,
+                           ctx: &mut SyncContext<DbExecutor>) -> Result<T, Error>
+    where M: Message<Result = Result<T, Error>>,
+          T: 'static,
+          DbExecutor: Handler<M, Result = Result<T, Error>>,
+{
+    let Traced { ctx: request, msg, mut pending } = traced;
+    let name = message_name::<M>();
+
+    pending.pick_up();
+
+    let result = RequestContext::scope(request.as_ref(), || {
+        let start = Instant::now();
+        let result = db.handle(msg, ctx);
+        let elapsed = start.elapsed();
+
+        if let Some(ref request) = request {
+            request.add_db_time(elapsed);
+        }
+
+        match result {
+            Ok(_) => debug!("{} done in {:.3}ms", name, elapsed.as_secs_f64() * 1000.0),
+            Err(ref e) => warn!("{} failed: {}", name, e),
+        }
+
+        result
+    });
+
+    drop(pending);
+    result
+}
+
+fn message_name<M>() -> &'static str {
+    let name = ::std::any::type_name::<M>();
+    name.rsplit("::").next().unwrap_or(name)
+}
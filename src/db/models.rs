@@ -1,5 +1,7 @@
 use chrono::NaiveDateTime;
-use diesel::sql_types::{Text, BigInt};
+use diesel::sql_types::{Array, Integer, Text, Timestamp};
+
+use models::Localized;
 
 use super::schema::*;
 
@@ -11,6 +13,8 @@ pub mod types {
     use diesel::deserialize::{self, FromSql};
     use diesel::serialize::{self, Output, ToSql};
     use diesel::sql_types::Varchar;
+    use language_tags::LanguageTag;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
     use error::ParseError;
 
@@ -164,32 +168,71 @@ pub mod types {
         }
     }
 
-    #[derive(AsExpression, FromSqlRow, Serialize, Deserialize,
-             Debug, Copy, Clone, Eq, PartialEq, Hash)]
+    /// Length of the language columns. Valid tags may be longer, but none in
+    /// practical use are.
+    pub const MAX_LANGUAGE_LENGTH: usize = 35;
+
+    /// A BCP 47 language tag in its canonical form, e.g. `en` or `pt-BR`.
+    #[derive(AsExpression, FromSqlRow, Debug, Clone, Eq, PartialEq, Hash)]
     #[sql_type = "Varchar"]
-    pub enum Language {
-        Russian,
-        English,
+    pub struct Language(String);
+
+    impl Language {
+        pub fn as_str(&self) -> &str {
+            &self.0
+        }
+
+        /// Returns the primary language subtag, e.g. `pt` for `pt-BR`.
+        pub fn primary(&self) -> &str {
+            self.0.split('-').next().unwrap_or(&self.0)
+        }
     }
 
     impl FromStr for Language {
         type Err = ParseError;
 
         fn from_str(s: &str) -> Result<Self, Self::Err> {
-            match s {
-                "ru" | "rus" | "russian" => Ok(Language::Russian),
-                "en" | "eng" | "english" => Ok(Language::English),
-                _ => Err(ParseError::UnknownLanguage { language: s.to_string() }),
+            let s = s.trim();
+
+            // names accepted before languages became BCP 47 tags
+            match s.to_lowercase().as_str() {
+                "rus" | "russian" => return Ok(Language("ru".to_string())),
+                "eng" | "english" => return Ok(Language("en".to_string())),
+                _ => {}
+            }
+
+            let tag = match s.parse::<LanguageTag>() {
+                Ok(ref tag) if tag.language.is_some() => tag.canonicalize().to_string(),
+                _ => return Err(ParseError::UnknownLanguage { language: s.to_string() }),
+            };
+
+            if tag.len() > MAX_LANGUAGE_LENGTH {
+                return Err(ParseError::LanguageTooLong {
+                    language: tag,
+                    max: MAX_LANGUAGE_LENGTH,
+                });
             }
+
+            Ok(Language(tag))
         }
     }
 
     impl fmt::Display for Language {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            write!(f, "{}", match *self {
-                Language::Russian => "ru",
-                Language::English => "en",
-            })
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl Serialize for Language {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&self.0)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Language {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            String::deserialize(deserializer)?.parse::<Language>()
+                .map_err(de::Error::custom)
         }
     }
 
@@ -242,6 +285,7 @@ pub struct Package {
     pub downloads: i32,
     pub created: NaiveDateTime,
     pub updated: NaiveDateTime,
+    pub likes: i32,
 }
 
 #[derive(Insertable, PartialEq, Debug)]
@@ -345,6 +389,22 @@ pub struct NewMaintainer<'a> {
     pub package: &'a str,
 }
 
+#[derive(Insertable, PartialEq, Debug)]
+#[table_name = "maintainer_invitations"]
+pub struct NewMaintainerInvitation<'a> {
+    pub package: &'a str,
+    pub user: i32,
+    pub invited_by: i32,
+}
+
+/// A previous name of `package`.
+#[derive(Insertable, PartialEq, Debug)]
+#[table_name = "package_aliases"]
+pub struct NewPackageAlias<'a> {
+    pub name: &'a str,
+    pub package: &'a str,
+}
+
 #[derive(Queryable, Identifiable, Associations, PartialEq, Debug)]
 #[primary_key(package, language)]
 #[belongs_to(Package, foreign_key = "package")]
@@ -358,7 +418,7 @@ pub struct Description {
 #[table_name = "descriptions"]
 pub struct NewDescription<'a> {
     pub package: &'a str,
-    pub language: types::Language,
+    pub language: &'a types::Language,
     pub description: &'a str,
 }
 
@@ -370,15 +430,19 @@ pub struct VersionText {
     pub language: types::Language,
     pub changes: String,
     pub readme: String,
+    pub changes_html: Option<String>,
+    pub readme_html: Option<String>,
 }
 
 #[derive(Insertable, PartialEq, Debug)]
 #[table_name = "version_texts"]
 pub struct NewVersionText<'a> {
     pub version: i32,
-    pub language: types::Language,
+    pub language: &'a types::Language,
     pub changes: &'a str,
     pub readme: &'a str,
+    pub changes_html: Option<String>,
+    pub readme_html: Option<String>,
 }
 
 #[derive(Queryable, Identifiable, Associations, PartialEq, Debug)]
@@ -394,14 +458,135 @@ pub struct DependencyDescription {
 #[table_name = "dependency_descriptions"]
 pub struct NewDependencyDescription<'a> {
     pub dependency: i32,
-    pub language: types::Language,
+    pub language: &'a types::Language,
     pub description: &'a str,
 }
 
+#[derive(QueryableByName, PartialEq, Debug)]
+pub struct PackageName {
+    #[sql_type = "Text"]
+    pub name: String,
+}
+
 #[derive(Queryable, QueryableByName, PartialEq, Debug)]
-pub struct GroupedLike {
+pub struct ConflictingNode {
     #[sql_type = "Text"]
     pub package: String,
-    #[sql_type = "BigInt"]
-    pub likes: i64,
-}
\ No newline at end of file
+    #[sql_type = "Text"]
+    pub version: String,
+    #[sql_type = "Text"]
+    pub path: String,
+}
+
+/// A package with its maintainers and descriptions, loaded in one query. Aggregated columns are JSON arrays.
+#[derive(QueryableByName, Debug)]
+pub struct PackageRow {
+    #[sql_type = "Text"]
+    pub name: String,
+    #[sql_type = "Text"]
+    pub website: String,
+    #[sql_type = "Text"]
+    pub license: String,
+    #[sql_type = "Array<Text>"]
+    pub authors: Vec<String>,
+    #[sql_type = "Integer"]
+    pub downloads: i32,
+    #[sql_type = "Timestamp"]
+    pub created: NaiveDateTime,
+    #[sql_type = "Timestamp"]
+    pub updated: NaiveDateTime,
+    #[sql_type = "Integer"]
+    pub likes: i32,
+    #[sql_type = "Array<Text>"]
+    pub maintainers: Vec<String>,
+    #[sql_type = "Text"]
+    pub descriptions: String,
+}
+
+/// A version with its texts, dependencies and contents as JSON arrays.
+#[derive(QueryableByName, Debug)]
+pub struct VersionRow {
+    #[sql_type = "Text"]
+    pub version: String,
+    #[sql_type = "Timestamp"]
+    pub created: NaiveDateTime,
+    #[sql_type = "Text"]
+    pub texts: String,
+    #[sql_type = "Text"]
+    pub dependencies: String,
+    #[sql_type = "Text"]
+    pub contents: String,
+}
+
+#[derive(Deserialize)]
+pub struct VersionTextJson {
+    pub language: types::Language,
+    pub changes: String,
+    pub readme: String,
+    pub changes_html: Option<String>,
+    pub readme_html: Option<String>,
+}
+
+/// Types are kept as in the database, which differs from their serde form.
+#[derive(Deserialize)]
+pub struct DependencyJson {
+    pub package: String,
+    pub spec: String,
+    #[serde(rename = "type")]
+    pub dep_type: String,
+    pub descriptions: Vec<Localized>,
+}
+
+#[derive(Deserialize)]
+pub struct ContentNodeJson {
+    #[serde(rename = "type")]
+    pub node_type: String,
+    pub path: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use error::ParseError;
+    use super::types::{Language, MAX_LANGUAGE_LENGTH};
+
+    fn parse(s: &str) -> Option<String> {
+        s.parse::<Language>().ok().map(|x| x.to_string())
+    }
+
+    #[test]
+    fn languages_are_canonicalized() {
+        assert_eq!(parse("en").as_deref(), Some("en"));
+        assert_eq!(parse(" pt-br ").as_deref(), Some("pt-BR"));
+        assert_eq!(parse("EN-us").as_deref(), Some("en-US"));
+        assert_eq!(parse("zh-hant-tw").as_deref(), Some("zh-Hant-TW"));
+    }
+
+    #[test]
+    fn old_language_names_are_mapped() {
+        for &(name, tag) in &[("eng", "en"), ("English", "en"), ("rus", "ru"),
+                              ("RUSSIAN", "ru")] {
+            assert_eq!(parse(name).as_deref(), Some(tag), "{}", name);
+        }
+    }
+
+    #[test]
+    fn invalid_languages_are_rejected() {
+        for tag in &["", "-", "en-", "x-private", "ninechars", "en_US", "1234"] {
+            assert_eq!(parse(tag), None, "{}", tag);
+        }
+    }
+
+    #[test]
+    fn long_languages_are_rejected() {
+        let fits = "en-US-x-aaaaaaaa-bbbbbbbb-cccccc-dd";
+        let long = "en-US-x-aaaaaaaa-bbbbbbbb-cccccc-ddd";
+        assert_eq!(fits.len(), MAX_LANGUAGE_LENGTH);
+
+        assert_eq!(parse(fits).as_deref(), Some(fits));
+
+        match long.parse::<Language>() {
+            Err(ParseError::LanguageTooLong { .. }) => {}
+            x => panic!("unexpected {:?}", x),
+        }
+    }
+}
@@ -1,3 +1,10 @@
+table! {
+    catalog (id) {
+        id -> Bool,
+        updated -> Timestamp,
+    }
+}
+
 table! {
     contents (id) {
         id -> Int4,
@@ -22,7 +29,7 @@ table! {
 table! {
     dependency_descriptions (dependency, language) {
         dependency -> Int4,
-        language -> Bpchar,
+        language -> Varchar,
         description -> Text,
     }
 }
@@ -30,7 +37,7 @@ table! {
 table! {
     descriptions (package, language) {
         package -> Text,
-        language -> Bpchar,
+        language -> Varchar,
         description -> Text,
     }
 }
@@ -43,6 +50,15 @@ table! {
     }
 }
 
+table! {
+    maintainer_invitations (package, user) {
+        package -> Text,
+        user -> Int4,
+        invited_by -> Nullable<Int4>,
+        created -> Timestamp,
+    }
+}
+
 table! {
     maintainers (user, package) {
         user -> Int4,
@@ -50,6 +66,14 @@ table! {
     }
 }
 
+table! {
+    package_aliases (name) {
+        name -> Text,
+        package -> Text,
+        created -> Timestamp,
+    }
+}
+
 table! {
     packages (name) {
         name -> Text,
@@ -59,6 +83,7 @@ table! {
         downloads -> Int4,
         created -> Timestamp,
         updated -> Timestamp,
+        likes -> Int4,
     }
 }
 
@@ -85,9 +110,11 @@ table! {
 table! {
     version_texts (version, language) {
         version -> Int4,
-        language -> Bpchar,
+        language -> Varchar,
         changes -> Text,
         readme -> Text,
+        changes_html -> Nullable<Text>,
+        readme_html -> Nullable<Text>,
     }
 }
 
@@ -98,18 +125,24 @@ joinable!(dependency_descriptions -> dependencies (dependency));
 joinable!(descriptions -> packages (package));
 joinable!(likes -> packages (package));
 joinable!(likes -> users (user));
+joinable!(maintainer_invitations -> packages (package));
+joinable!(maintainer_invitations -> users (user));
 joinable!(maintainers -> packages (package));
 joinable!(maintainers -> users (user));
+joinable!(package_aliases -> packages (package));
 joinable!(version_texts -> versions (version));
 joinable!(versions -> packages (package));
 
 allow_tables_to_appear_in_same_query!(
+    catalog,
     contents,
     dependencies,
     dependency_descriptions,
     descriptions,
     likes,
+    maintainer_invitations,
     maintainers,
+    package_aliases,
     packages,
     users,
     versions,
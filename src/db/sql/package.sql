@@ -0,0 +1,14 @@
+-- The package row of GetPackage, with its maintainers and descriptions.
+-- $1 is the requested name, which may differ in case or be a former name.
+-- Shared with benches/get_package/run.sh.
+SELECT p.name, p.website, p.license, p.authors, p.downloads, p.created, p.updated,
+    p.likes,
+    ARRAY(SELECT u.username FROM maintainers m
+        INNER JOIN users u ON u.id = m."user"
+        WHERE m.package = p.name ORDER BY m."user") AS maintainers,
+    COALESCE((SELECT json_agg(json_build_object(
+            'language', d.language, 'text', d.description) ORDER BY d.language)
+        FROM descriptions d WHERE d.package = p.name), '[]')::text AS descriptions
+    FROM packages p WHERE lower(p.name) = lower($1)
+        OR p.name = (SELECT a.package FROM package_aliases a
+            WHERE lower(a.name) = lower($1))
@@ -0,0 +1,22 @@
+-- The versions of a package with their texts, dependencies and contents.
+-- $1 is the canonical package name. Every aggregate is ordered by the
+-- table's key so the output doesn't depend on the plan.
+-- Shared with benches/get_package/run.sh.
+SELECT v.version, v.created,
+    COALESCE((SELECT json_agg(json_build_object(
+            'language', t.language, 'changes', t.changes, 'readme', t.readme,
+            'changes_html', t.changes_html, 'readme_html', t.readme_html)
+            ORDER BY t.language)
+        FROM version_texts t WHERE t.version = v.id), '[]')::text AS texts,
+    COALESCE((SELECT json_agg(json_build_object(
+            'package', d.package, 'spec', d.spec, 'type', d.type,
+            'descriptions', COALESCE((SELECT json_agg(json_build_object(
+                    'language', dd.language, 'text', dd.description)
+                    ORDER BY dd.language)
+                FROM dependency_descriptions dd WHERE dd.dependency = d.id), '[]')
+        ) ORDER BY d.id)
+        FROM dependencies d WHERE d.version = v.id), '[]')::text AS dependencies,
+    COALESCE((SELECT json_agg(json_build_object('type', c.type, 'path', c.path)
+            ORDER BY c.id)
+        FROM contents c WHERE c.version = v.id), '[]')::text AS contents
+    FROM versions v WHERE v.package = $1 ORDER BY v.id
@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Fail, Debug)]
 pub enum ParseError {
     #[fail(display = "unknown group: {}", group)]
@@ -12,8 +14,185 @@ pub enum ParseError {
     UnknownNodeType {
         name: String,
     },
-    #[fail(display = "unknown language: {}", language)]
+    #[fail(display = "invalid language tag: {}", language)]
     UnknownLanguage {
         language: String,
     },
-}
\ No newline at end of file
+    #[fail(display = "language tag is longer than {} characters: {}", max, language)]
+    LanguageTooLong {
+        language: String,
+        max: usize,
+    },
+}
+
+#[derive(Fail, Debug)]
+pub enum ContentError {
+    #[fail(display = "{}: path is empty", path)]
+    EmptyPath {
+        path: String,
+    },
+    #[fail(display = "{}: path contains a forbidden character", path)]
+    ForbiddenCharacter {
+        path: String,
+    },
+    #[fail(display = "{}: absolute paths are not allowed", path)]
+    AbsolutePath {
+        path: String,
+    },
+    #[fail(display = "{}: `..` segments are not allowed", path)]
+    ParentReference {
+        path: String,
+    },
+    #[fail(display = "{}: path is declared more than once", path)]
+    DuplicatePath {
+        path: String,
+    },
+    #[fail(display = "{}: parent {} is declared as a file", path, parent)]
+    ParentIsFile {
+        path: String,
+        parent: String,
+    },
+}
+
+impl ContentError {
+    /// The path the error is about, as declared.
+    pub fn path(&self) -> &str {
+        match *self {
+            ContentError::EmptyPath { ref path } |
+            ContentError::ForbiddenCharacter { ref path } |
+            ContentError::AbsolutePath { ref path } |
+            ContentError::ParentReference { ref path } |
+            ContentError::DuplicatePath { ref path } |
+            ContentError::ParentIsFile { ref path, .. } => path,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ContentErrors(pub Vec<ContentError>);
+
+impl fmt::Display for ContentErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, err) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", err)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Fail, Debug)]
+pub enum ValidationError {
+    #[fail(display = "invalid contents of version {}: {}", version, errors)]
+    InvalidContents {
+        version: String,
+        errors: ContentErrors,
+    },
+    #[fail(display = "package name `{}` must be {} to {} characters long", name, min, max)]
+    NameLength {
+        name: String,
+        min: usize,
+        max: usize,
+    },
+    #[fail(display = "package name `{}` may only contain ASCII letters, digits, `-` and `_`, \
+                      must start with a letter and end with a letter or a digit", name)]
+    NameCharacters {
+        name: String,
+    },
+    #[fail(display = "package name `{}` is reserved, choose another one", name)]
+    ReservedName {
+        name: String,
+    },
+    #[fail(display = "package name `{}` is taken by `{}` (names are case-insensitive)",
+           name, existing)]
+    NameTaken {
+        name: String,
+        existing: String,
+    },
+    #[fail(display = "package name `{}` is too similar to `{}`, choose a more distinct one",
+           name, existing)]
+    SimilarName {
+        name: String,
+        existing: String,
+    },
+    #[fail(display = "invalid cursor: {}", cursor)]
+    InvalidCursor {
+        cursor: String,
+    },
+    #[fail(display = "dependency `{}` is not a published package", package)]
+    UnknownDependency {
+        package: String,
+    },
+    #[fail(display = "{} is a dependency of {}; delete or update them first", name, dependents)]
+    HasDependents {
+        name: String,
+        dependents: String,
+    },
+}
+
+#[derive(Fail, Debug)]
+pub enum AuthError {
+    #[fail(display = "authentication required")]
+    Required,
+    #[fail(display = "invalid username or password")]
+    InvalidCredentials,
+    #[fail(display = "user {} is banned", username)]
+    Banned {
+        username: String,
+    },
+}
+
+#[derive(Fail, Debug)]
+#[fail(display = "too many requests, retry in {} seconds", retry_after)]
+pub struct RateLimited {
+    pub retry_after: u64,
+}
+
+#[derive(Fail, Debug)]
+pub enum MaintainerError {
+    #[fail(display = "only maintainers of {} may change its maintainers", package)]
+    NotMaintainer {
+        package: String,
+    },
+    #[fail(display = "{} already maintains {}", username, package)]
+    AlreadyMaintainer {
+        username: String,
+        package: String,
+    },
+    #[fail(display = "{} is neither a maintainer of {} nor invited to be one", username, package)]
+    NotListed {
+        username: String,
+        package: String,
+    },
+    #[fail(display = "{} has no invitation to maintain {}", username, package)]
+    NoInvitation {
+        username: String,
+        package: String,
+    },
+    #[fail(display = "{} is the last maintainer of {}; invite another one first",
+           username, package)]
+    LastMaintainer {
+        username: String,
+        package: String,
+    },
+}
+
+#[derive(Fail, Debug)]
+pub enum ConfigError {
+    #[fail(display = "failed to read config file {}: {}", path, cause)]
+    Read {
+        path: String,
+        cause: String,
+    },
+    #[fail(display = "failed to parse config file {}: {}", path, cause)]
+    Parse {
+        path: String,
+        cause: String,
+    },
+    #[fail(display = "invalid configuration: {}", reason)]
+    Invalid {
+        reason: String,
+    },
+}
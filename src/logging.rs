@@ -0,0 +1,254 @@
+use std::cell::RefCell;
+use std::env;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
+use actix_web::error::Result;
+use actix_web::http::header::HeaderValue;
+use actix_web::middleware::{Finished, Middleware, Response, Started};
+use env_logger::Builder;
+use log::Record;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_json::{Map, Value};
+
+use config::config_groups::{LogFormat, LogGroup};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+thread_local! {
+    /// Request whose work is currently being done on this thread.
+    static CURRENT: RefCell<Option<Arc<RequestContext>>> = const { RefCell::new(None) };
+
+    /// Structured fields attached to the next log record.
+    static FIELDS: RefCell<Option<Map<String, Value>>> = const { RefCell::new(None) };
+}
+
+/// Installs the global logger. `RUST_LOG`, if set, overrides `log.filter`.
+pub fn init(config: &LogGroup) {
+    let mut builder = Builder::new();
+    builder.parse(&config.filter);
+
+    if let Ok(filter) = env::var("RUST_LOG") {
+        builder.parse(&filter);
+    }
+
+    match config.format {
+        LogFormat::Json => builder.format(|buf, record| {
+            let line = json_line(buf.timestamp().to_string(), record);
+            writeln!(buf, "{}", line)
+        }),
+        LogFormat::Text => builder.format(|buf, record| {
+            let line = text_line(buf.timestamp().to_string(), record);
+            writeln!(buf, "{}", line)
+        }),
+    };
+
+    builder.init();
+}
+
+/// Formats a record like `env_logger` does, with the request id and user
+/// id, if known, in front of the message.
+fn text_line(time: String, record: &Record) -> String {
+    let mut line = format!("{:>5} {}: {}: ", record.level(), time,
+                           record.module_path().unwrap_or_else(|| record.target()));
+
+    CURRENT.with(|current| {
+        if let Some(ref ctx) = *current.borrow() {
+            match ctx.user() {
+                Some((id, _)) => line.push_str(&format!("[{} user {}] ", ctx.id, id)),
+                None => line.push_str(&format!("[{}] ", ctx.id)),
+            }
+        }
+    });
+
+    line.push_str(&record.args().to_string());
+    line
+}
+
+fn json_line(time: String, record: &Record) -> String {
+    let mut line = Map::new();
+    line.insert("time".into(), Value::String(time));
+    line.insert("level".into(), Value::String(record.level().to_string()));
+    line.insert("target".into(), Value::String(record.target().to_string()));
+    line.insert("message".into(), Value::String(record.args().to_string()));
+
+    CURRENT.with(|current| {
+        if let Some(ref ctx) = *current.borrow() {
+            line.insert("request_id".into(), Value::String(ctx.id.clone()));
+        }
+    });
+
+    FIELDS.with(|fields| {
+        if let Some(ref fields) = *fields.borrow() {
+            line.extend(fields.clone());
+        }
+    });
+
+    Value::Object(line).to_string()
+}
+
+/// Per-request data shared between the HTTP worker and database executors.
+pub struct RequestContext {
+    pub id: String,
+    db_micros: AtomicUsize,
+    user: Mutex<Option<(i32, String)>>,
+}
+
+impl RequestContext {
+    pub fn new(id: String) -> RequestContext {
+        RequestContext {
+            id,
+            db_micros: AtomicUsize::new(0),
+            user: Mutex::new(None),
+        }
+    }
+
+    /// Records the id and name of the user the request was authenticated as.
+    pub fn set_user(&self, id: i32, username: &str) {
+        *self.user.lock().unwrap_or_else(|e| e.into_inner()) = Some((id, username.to_string()));
+    }
+
+    pub fn user(&self) -> Option<(i32, String)> {
+        self.user.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn add_db_time(&self, time: Duration) {
+        self.db_micros.fetch_add(time.as_micros() as usize, Ordering::Relaxed);
+    }
+
+    pub fn db_time(&self) -> Duration {
+        Duration::from_micros(self.db_micros.load(Ordering::Relaxed) as u64)
+    }
+
+    /// Returns the context of the request being served.
+    pub fn of<S>(req: &HttpRequest<S>) -> Option<Arc<RequestContext>> {
+        req.extensions_ro().get::<Arc<RequestContext>>().cloned()
+    }
+
+    /// Runs `f` with this request as the subject of log records emitted on
+    /// the current thread.
+    pub fn scope<T, F: FnOnce() -> T>(ctx: Option<&Arc<RequestContext>>, f: F) -> T {
+        let previous = CURRENT.with(|current| {
+            current.replace(ctx.cloned())
+        });
+        let result = f();
+        CURRENT.with(|current| current.replace(previous));
+        result
+    }
+}
+
+fn generate_request_id() -> String {
+    let mut bytes = [0u8; 16];
+
+    if SystemRandom::new().fill(&mut bytes).is_err() {
+        return "unknown".to_string();
+    }
+
+    bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+/// Accepts client-supplied request ids only if they are short and contain no
+/// characters that would need escaping in logs.
+fn valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 128
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+struct StartTime(Instant);
+
+/// Assigns every request an id, taken from `X-Request-Id` or generated,
+/// echoes it in the response and writes one access log record per request.
+pub struct RequestLogger;
+
+impl<S> Middleware<S> for RequestLogger {
+    fn start(&self, req: &mut HttpRequest<S>) -> Result<Started> {
+        let id = req.headers().get(REQUEST_ID_HEADER)
+            .and_then(|x| x.to_str().ok())
+            .filter(|x| valid_request_id(x))
+            .map(|x| x.to_string())
+            .unwrap_or_else(generate_request_id);
+
+        req.extensions().insert(Arc::new(RequestContext::new(id)));
+        req.extensions().insert(StartTime(Instant::now()));
+
+        Ok(Started::Done)
+    }
+
+    fn response(&self, req: &mut HttpRequest<S>, mut resp: HttpResponse) -> Result<Response> {
+        if let Some(ctx) = RequestContext::of(req) {
+            if let Ok(value) = HeaderValue::from_str(&ctx.id) {
+                resp.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
+        }
+
+        Ok(Response::Done(resp))
+    }
+
+    fn finish(&self, req: &mut HttpRequest<S>, resp: &HttpResponse) -> Finished {
+        let ctx = RequestContext::of(req);
+        let latency = req.extensions_ro().get::<StartTime>()
+            .map(|x| x.0.elapsed())
+            .unwrap_or_default();
+        let db_time = ctx.as_ref().map(|x| x.db_time()).unwrap_or_default();
+        let (user_id, username) = match ctx.as_ref().and_then(|x| x.user()) {
+            Some((id, username)) => (Value::from(id), Value::String(username)),
+            None => (Value::Null, Value::Null),
+        };
+        let route = match req.resource().name() {
+            "" => "unmatched",
+            name => name,
+        };
+
+        let mut fields = Map::new();
+        fields.insert("method".into(), Value::String(req.method().to_string()));
+        fields.insert("path".into(), Value::String(req.path().to_string()));
+        fields.insert("route".into(), Value::String(route.to_string()));
+        fields.insert("user_id".into(), user_id);
+        fields.insert("username".into(), username);
+        fields.insert("status".into(), Value::from(resp.status().as_u16()));
+        fields.insert("latency_ms".into(), Value::from(latency.as_secs_f64() * 1000.0));
+        fields.insert("db_ms".into(), Value::from(db_time.as_secs_f64() * 1000.0));
+
+        FIELDS.with(|x| *x.borrow_mut() = Some(fields));
+        RequestContext::scope(ctx.as_ref(), || {
+            info!(target: "access", "{} {} {} {:.3}ms (db {:.3}ms)",
+                  req.method(), req.path(), resp.status().as_u16(),
+                  latency.as_secs_f64() * 1000.0, db_time.as_secs_f64() * 1000.0);
+        });
+        FIELDS.with(|x| *x.borrow_mut() = None);
+
+        Finished::Done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use log::Level;
+
+    fn line(ctx: Option<&Arc<RequestContext>>) -> String {
+        RequestContext::scope(ctx, || {
+            text_line("now".into(), &Record::builder()
+                .args(format_args!("GetPackage done"))
+                .level(Level::Debug)
+                .target("hel2_back::db")
+                .module_path(Some("hel2_back::db"))
+                .build())
+        })
+    }
+
+    #[test]
+    fn text_lines_name_the_request() {
+        assert_eq!(line(None), "DEBUG now: hel2_back::db: GetPackage done");
+
+        let ctx = Arc::new(RequestContext::new("abc".into()));
+        assert_eq!(line(Some(&ctx)), "DEBUG now: hel2_back::db: [abc] GetPackage done");
+
+        ctx.set_user(7, "someone");
+        assert_eq!(line(Some(&ctx)), "DEBUG now: hel2_back::db: [abc user 7] GetPackage done");
+    }
+}
@@ -1,6 +1,19 @@
 #[macro_use] extern crate diesel;
+#[macro_use] extern crate diesel_migrations;
 extern crate env_logger;
+#[macro_use] extern crate log;
 extern crate chrono;
+extern crate language_tags;
+extern crate ammonia;
+extern crate pulldown_cmark;
+extern crate ring;
+extern crate base64;
+#[cfg(feature = "tls")]
+extern crate openssl;
+extern crate clap;
+extern crate rpassword;
+#[macro_use] extern crate lazy_static;
+#[macro_use] extern crate prometheus;
 
 extern crate failure;
 #[macro_use] extern crate failure_derive;
@@ -11,45 +24,121 @@ extern crate actix_web;
 
 extern crate serde;
 extern crate toml;
-extern crate serde_json;
+#[cfg_attr(test, macro_use)] extern crate serde_json;
 #[macro_use] extern crate serde_derive;
 
 mod error;
 mod config;
 mod db;
 mod app;
+mod cli;
+mod logging;
 mod resources;
+mod markdown;
+mod metrics;
 mod models;
+mod password;
+mod ratelimit;
+mod tls;
+mod validate;
 
-use actix::{System, SyncArbiter};
-use actix_web::server;
+use std::time::Duration;
+
+use actix::System;
+use actix_web::server::{self, KeepAlive};
 
 use app::State;
 
 fn main() {
-    env_logger::init();
+    let matches = cli::app().get_matches();
 
-    let config_path = ::std::env::var("HEL_CONFIG_PATH")
+    let config_path = matches.value_of("config").map(|x| x.to_string())
+        .or_else(|| ::std::env::var("HEL_CONFIG_PATH").ok())
         .unwrap_or("config.toml".into());
-    let config = config::Config::load(config_path).unwrap();
+    let config = match config::Config::load(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            ::std::process::exit(1);
+        }
+    };
 
-    let sys = System::new("hel2-back");
+    logging::init(&config.log);
+
+    for name in config::Config::ignored_env() {
+        warn!("ignoring {}, which doesn't name a config field", name);
+    }
+
+    if let Some(result) = cli::run(&matches, &config) {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            ::std::process::exit(1);
+        }
+
+        return;
+    }
 
-    let db_config = config.database.clone();
-    let db = SyncArbiter::start(config.database.threads, move || {
-        db::DbExecutor {
-            conn: db::establish_connection(&db_config.url).unwrap()
+    if config.database.migrate_on_startup {
+        if let Err(e) = cli::migrate(&config) {
+            eprintln!("{}", e);
+            ::std::process::exit(1);
         }
-    });
+    }
+
+    let sys = System::new("hel2-back");
+
+    let db = db::start(&config.database, &config.cache, &config.names,
+                       config.database.threads);
 
     let state = State::new(config.clone(), db);
 
-    let mut b = server::new(move || app::create(state.clone()));
+    let keep_alive = match config.http.keep_alive {
+        0 => KeepAlive::Disabled,
+        secs => KeepAlive::Timeout(secs),
+    };
+
+    let mut b = server::new(move || app::create(state.clone()))
+        .keep_alive(keep_alive)
+        .shutdown_timeout(config.http.shutdown_timeout);
 
     if let Some(http_threads) = config.http.threads {
         b = b.threads(http_threads);
     }
 
-    b.bind(config.http.bind_address).unwrap().start();
+    let b = match b.bind(&config.http.bind_address) {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("cannot bind {}: {}", config.http.bind_address, e);
+            ::std::process::exit(1);
+        }
+    };
+
+    let started = if config.http.tls_enabled() {
+        tls::start(b, &config.http).and_then(|_| tls::start_redirect(&config.http))
+    } else {
+        b.start();
+        Ok(None)
+    };
+
+    let redirect = match started {
+        Ok(redirect) => redirect,
+        Err(e) => {
+            eprintln!("{}", e);
+            ::std::process::exit(1);
+        }
+    };
+
+    // stops gracefully on SIGTERM, waiting up to `shutdown_timeout` for
+    // in-flight requests
     sys.run();
-}
\ No newline at end of file
+
+    let grace = Duration::from_secs(u64::from(config.http.shutdown_timeout));
+
+    if !db::drain(grace) {
+        warn!("exiting with unfinished database messages");
+    }
+
+    if let Some(redirect) = redirect {
+        redirect.stop();
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+
+use ammonia::{Builder, UrlRelative};
+use pulldown_cmark::{html, Options, Parser};
+
+/// Renders Markdown to HTML that is safe to embed into a web page.
+///
+/// Scripts, styles and event handlers are stripped, links and images may only
+/// point to absolute `http`, `https` or `mailto` URLs, and every link gets
+/// `rel="noopener noreferrer nofollow"`.
+pub fn render(text: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+
+    let mut unsafe_html = String::with_capacity(text.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, Parser::new_ext(text, options));
+
+    let schemes: HashSet<&str> = ["http", "https", "mailto"].iter().cloned().collect();
+
+    Builder::default()
+        .url_schemes(schemes)
+        .url_relative(UrlRelative::Deny)
+        .link_rel(Some("noopener noreferrer nofollow"))
+        .clean(&unsafe_html)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_scripts() {
+        let html = render("before <script>alert(1)</script> after");
+        assert!(!html.contains("script"), "{}", html);
+        assert!(!html.contains("alert"), "{}", html);
+        assert!(html.contains("before"), "{}", html);
+    }
+
+    #[test]
+    fn strips_event_handlers() {
+        let html = render("<img src=\"https://example.com/a.png\" onerror=\"alert(1)\">");
+        assert!(!html.contains("onerror"), "{}", html);
+        assert!(html.contains("src=\"https://example.com/a.png\""), "{}", html);
+    }
+
+    #[test]
+    fn strips_unsafe_urls() {
+        for text in &[
+            "[link](javascript:alert(1))",
+            "<a href=\"javascript:alert(1)\">link</a>",
+            "[link](data:text/html;base64,PHNjcmlwdD4=)",
+            "![image](javascript:alert(1))",
+            "![image](data:image/svg+xml;base64,PHN2Zz4=)",
+        ] {
+            let html = render(text);
+            assert!(!html.contains("javascript:"), "{}", html);
+            assert!(!html.contains("data:"), "{}", html);
+        }
+    }
+
+    #[test]
+    fn strips_relative_urls() {
+        let html = render("[link](/api/packages) ![image](logo.png)");
+        assert!(!html.contains("href"), "{}", html);
+        assert!(!html.contains("src"), "{}", html);
+    }
+
+    #[test]
+    fn keeps_absolute_links_with_rel() {
+        let html = render("[hel](https://hel.example/) <a href=\"mailto:a@b.c\" rel=\"x\">mail</a>");
+        assert!(html.contains("href=\"https://hel.example/\""), "{}", html);
+        assert!(html.contains("href=\"mailto:a@b.c\""), "{}", html);
+        assert_eq!(html.matches("rel=\"noopener noreferrer nofollow\"").count(), 2, "{}", html);
+        assert!(!html.contains("rel=\"x\""), "{}", html);
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web::error::Result;
+use actix_web::middleware::{Finished, Middleware, Started};
+use prometheus::{Encoder, HistogramTimer, HistogramVec, IntCounterVec, IntGauge, TextEncoder};
+
+lazy_static! {
+    static ref HTTP_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "hel_http_requests_total",
+        "Number of handled HTTP requests",
+        &["route", "method", "status"]
+    ).unwrap();
+
+    static ref HTTP_DURATION: HistogramVec = register_histogram_vec!(
+        "hel_http_request_duration_seconds",
+        "Time spent handling HTTP requests",
+        &["route", "method", "status"]
+    ).unwrap();
+
+    /// Messages sent to the database executors and not yet picked up.
+    pub static ref DB_MAILBOX_DEPTH: IntGauge = register_int_gauge!(
+        "hel_db_mailbox_depth",
+        "Number of messages waiting for a database executor"
+    ).unwrap();
+
+    /// Messages sent to the database executors and not yet fully handled.
+    pub static ref DB_IN_FLIGHT: IntGauge = register_int_gauge!(
+        "hel_db_messages_in_flight",
+        "Number of database messages waiting or being handled"
+    ).unwrap();
+
+    static ref DB_DURATION: HistogramVec = register_histogram_vec!(
+        "hel_db_message_duration_seconds",
+        "Time spent handling database messages",
+        &["message"]
+    ).unwrap();
+
+    static ref PACKAGES: IntGauge = register_int_gauge!(
+        "hel_packages",
+        "Number of packages"
+    ).unwrap();
+
+    static ref USERS: IntGauge = register_int_gauge!(
+        "hel_users",
+        "Number of registered users"
+    ).unwrap();
+
+    /// When the business gauges were last counted.
+    static ref COUNTED: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+/// How long the business gauges are reported without counting again. Counting
+/// scans whole tables, which scrapes every few seconds shouldn't repeat.
+const COUNTS_MAX_AGE: Duration = Duration::from_secs(60);
+
+/// Starts timing a database message. The returned timer records the handling
+/// time when dropped.
+pub fn db_message(name: &'static str) -> HistogramTimer {
+    DB_DURATION.with_label_values(&[name]).start_timer()
+}
+
+/// Returns whether the business gauges should be counted again.
+pub fn counts_due() -> bool {
+    match *COUNTED.lock().unwrap_or_else(|e| e.into_inner()) {
+        Some(counted) => counted.elapsed() >= COUNTS_MAX_AGE,
+        None => true,
+    }
+}
+
+pub fn set_counts(packages: i64, users: i64) {
+    PACKAGES.set(packages);
+    USERS.set(users);
+    *COUNTED.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+}
+
+pub const CONTENT_TYPE: &str = ::prometheus::TEXT_FORMAT;
+
+/// Renders every registered metric in the Prometheus text format.
+pub fn render() -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    // encoding into a Vec cannot fail
+    TextEncoder::new().encode(&::prometheus::gather(), &mut buffer).unwrap();
+
+    buffer
+}
+
+struct StartTime(Instant);
+
+/// Records request counts and latencies per named resource.
+pub struct Metrics;
+
+impl<S> Middleware<S> for Metrics {
+    fn start(&self, req: &mut HttpRequest<S>) -> Result<Started> {
+        req.extensions().insert(StartTime(Instant::now()));
+        Ok(Started::Done)
+    }
+
+    fn finish(&self, req: &mut HttpRequest<S>, resp: &HttpResponse) -> Finished {
+        let elapsed = match req.extensions().get::<StartTime>() {
+            Some(start) => start.0.elapsed(),
+            None => return Finished::Done,
+        };
+
+        let route = match req.resource().name() {
+            "" => "unmatched",
+            name => name,
+        };
+        let status = resp.status();
+        let labels = [route, req.method().as_str(), status.as_str()];
+
+        HTTP_REQUESTS.with_label_values(&labels).inc();
+        HTTP_DURATION.with_label_values(&labels).observe(elapsed.as_secs_f64());
+
+        Finished::Done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_are_reused_for_a_while() {
+        set_counts(3, 2);
+        assert!(!counts_due());
+        assert_eq!(PACKAGES.get(), 3);
+        assert_eq!(USERS.get(), 2);
+
+        *COUNTED.lock().unwrap() = Instant::now().checked_sub(COUNTS_MAX_AGE);
+        assert!(counts_due());
+    }
+}
@@ -6,10 +6,14 @@ pub mod date_serde {
 
     const FORMAT: &'static str = "%+";
 
+    /// `%+` needs an offset, which a `NaiveDateTime` doesn't have. Dates are
+    /// stored in UTC.
+    const UTC_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f+00:00";
+
     pub fn serialize<S>(date: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error>
         where S: Serializer
     {
-        let s = format!("{}", date.format(FORMAT));
+        let s = format!("{}", date.format(UTC_FORMAT));
         serializer.serialize_str(&s)
     }
 
@@ -21,13 +25,43 @@ pub mod date_serde {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Localized {
     pub language: Language,
     pub text: String,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Keeps only the text that best matches the preferred languages.
+///
+/// Each preference is tried in order, first exactly and then by its primary
+/// subtag. If none of them match, English is picked, and failing that, the
+/// first text.
+pub fn select_language(texts: &mut Vec<Localized>, preferred: &[Language]) {
+    if texts.len() <= 1 {
+        return;
+    }
+
+    let best = preferred.iter()
+        .filter_map(|lang| {
+            texts.iter().position(|x| x.language == *lang)
+                .or_else(|| texts.iter().position(|x| x.language.primary() == lang.primary()))
+        })
+        .next()
+        .or_else(|| texts.iter().position(|x| x.language.primary() == "en"))
+        .unwrap_or(0);
+
+    let text = texts.swap_remove(best);
+    texts.clear();
+    texts.push(text);
+}
+
+/// Response models carrying localized texts.
+pub trait Localize {
+    /// Collapses every localized text to a single language.
+    fn localize(&mut self, preferred: &[Language]);
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ContentNode {
     #[serde(rename = "type")]
     pub node_type: NodeType,
@@ -39,7 +73,7 @@ pub mod user {
 
     use ::db::models::types::UserGroup;
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, Clone)]
     pub struct Full {
         pub username: String,
         pub group: UserGroup,
@@ -47,7 +81,7 @@ pub mod user {
         pub registered: NaiveDateTime,
     }
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, Clone)]
     pub struct Short {
         pub username: String,
     }
@@ -56,9 +90,10 @@ pub mod user {
 pub mod package {
     use chrono::NaiveDateTime;
 
-    use super::Localized;
+    use ::db::models::types::Language;
+    use super::{select_language, Localize, Localized};
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, Clone)]
     pub struct Full {
         pub name: String,
         pub description: Vec<Localized>,
@@ -75,7 +110,7 @@ pub mod package {
         pub updated: NaiveDateTime,
     }
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, Clone)]
     pub struct Short {
         pub name: String,
         pub description: Vec<Localized>,
@@ -83,19 +118,40 @@ pub mod package {
         pub versions: Vec<super::version::Short>,
         pub downloads: i32,
         pub likes: i32,
+        #[serde(with = "super::date_serde")]
+        pub updated: NaiveDateTime,
+    }
+
+    impl Localize for Full {
+        fn localize(&mut self, preferred: &[Language]) {
+            select_language(&mut self.description, preferred);
+
+            for version in self.versions.iter_mut() {
+                version.localize(preferred);
+            }
+        }
+    }
+
+    impl Localize for Short {
+        fn localize(&mut self, preferred: &[Language]) {
+            select_language(&mut self.description, preferred);
+        }
     }
 }
 
 pub mod version {
     use chrono::NaiveDateTime;
 
-    use super::{ContentNode, Localized};
+    use ::db::models::types::Language;
+    use super::{select_language, ContentNode, Localize, Localized};
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, Clone)]
     pub struct Full {
         pub version: String,
         pub changes: Vec<Localized>,
         pub readme: Vec<Localized>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub rendered: Option<Rendered>,
         pub url: String,
         pub dependencies: Vec<super::dependency::Full>,
         pub contents: Vec<ContentNode>,
@@ -103,7 +159,31 @@ pub mod version {
         pub created: NaiveDateTime,
     }
 
-    #[derive(Serialize, Deserialize)]
+    /// `changes` and `readme` rendered to sanitized HTML. Filled in by the
+    /// server; ignored when publishing.
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct Rendered {
+        pub changes: Vec<Localized>,
+        pub readme: Vec<Localized>,
+    }
+
+    impl Localize for Full {
+        fn localize(&mut self, preferred: &[Language]) {
+            select_language(&mut self.changes, preferred);
+            select_language(&mut self.readme, preferred);
+
+            if let Some(rendered) = self.rendered.as_mut() {
+                select_language(&mut rendered.changes, preferred);
+                select_language(&mut rendered.readme, preferred);
+            }
+
+            for dep in self.dependencies.iter_mut() {
+                dep.localize(preferred);
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
     pub struct Short {
         pub version: String,
         pub url: String,
@@ -112,11 +192,11 @@ pub mod version {
 }
 
 pub mod dependency {
-    use ::db::models::types::DependencyType;
+    use ::db::models::types::{DependencyType, Language};
 
-    use super::Localized;
+    use super::{select_language, Localize, Localized};
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, Clone)]
     pub struct Full {
         // package::Short would be nice here...
         pub package: String,
@@ -126,31 +206,258 @@ pub mod dependency {
         pub description: Option<Vec<Localized>>,
     }
 
-    #[derive(Serialize, Deserialize)]
+    impl Localize for Full {
+        fn localize(&mut self, preferred: &[Language]) {
+            if let Some(description) = self.description.as_mut() {
+                select_language(description, preferred);
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
     pub struct Short {
         pub package: String,
         pub spec: String,
     }
 }
 
+pub mod conflict {
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct Conflict {
+        pub package: String,
+        pub version: String,
+        pub paths: Vec<String>,
+    }
+}
+
+pub mod maintainer {
+    use super::user;
+
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct List {
+        pub maintainers: Vec<user::Short>,
+        /// Users invited to become maintainers who haven't accepted yet.
+        pub invited: Vec<user::Short>,
+    }
+}
+
+pub mod service {
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct Status {
+        pub status: String,
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct Version {
+        pub version: String,
+        pub schema_version: Option<String>,
+    }
+}
+
 pub mod api {
+    use std::str::FromStr;
+
+    use chrono::NaiveDateTime;
+
+    use error::ValidationError;
+    use super::package;
+
     #[derive(Deserialize)]
     pub struct PaginationRq {
+        #[serde(default = "PaginationRq::first_page")]
         pub page: u32,
         pub limit: u32,
+        /// Switches to cursor pagination. Empty for the first page.
+        #[serde(default)]
+        pub cursor: Option<String>,
     }
 
     impl PaginationRq {
+        fn first_page() -> u32 {
+            1
+        }
+
         pub fn validate(self, page_limit: u32) -> PaginationRq {
             PaginationRq {
                 page: self.page.max(1),
                 limit: self.limit.min(page_limit).max(1),
+                cursor: self.cursor,
+            }
+        }
+    }
+
+    /// Position in the package listing, which is ordered by `(created, name)`
+    /// descending. Clients only see it as an opaque token.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Cursor {
+        pub created: NaiveDateTime,
+        pub name: String,
+    }
+
+    impl Cursor {
+        pub fn encode(&self) -> String {
+            let raw = format!("{}.{}:{}",
+                              self.created.timestamp(),
+                              self.created.timestamp_subsec_nanos(),
+                              self.name);
+
+            raw.bytes().map(|x| format!("{:02x}", x)).collect()
+        }
+    }
+
+    impl FromStr for Cursor {
+        type Err = ValidationError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let invalid = || ValidationError::InvalidCursor { cursor: s.to_string() };
+
+            if !s.len().is_multiple_of(2) || !s.is_ascii() {
+                return Err(invalid());
             }
+
+            let bytes = (0..s.len()).step_by(2)
+                .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid())?;
+            let raw = String::from_utf8(bytes).map_err(|_| invalid())?;
+
+            let (time, name) = raw.split_once(':').ok_or_else(invalid)?;
+            let (secs, nanos) = time.split_once('.').ok_or_else(invalid)?;
+            let created = match (secs.parse(), nanos.parse()) {
+                (Ok(secs), Ok(nanos)) => NaiveDateTime::from_timestamp_opt(secs, nanos),
+                _ => None,
+            }.ok_or_else(invalid)?;
+
+            Ok(Cursor {
+                created,
+                name: name.to_string(),
+            })
         }
     }
 
+    /// A page of the package listing in cursor mode.
+    #[derive(Serialize)]
+    pub struct PackagePage {
+        pub packages: Vec<package::Short>,
+        /// Cursor of the next page, or `None` if this one is the last.
+        pub next: Option<String>,
+    }
+
     #[derive(Deserialize)]
     pub struct Name {
         pub name: String,
     }
-}
\ No newline at end of file
+
+    #[derive(Deserialize)]
+    pub struct NameVersion {
+        pub name: String,
+        pub version: String,
+    }
+
+    #[derive(Deserialize)]
+    pub struct NameUsername {
+        pub name: String,
+        pub username: String,
+    }
+
+    /// Invites `username` to maintain a package, or accepts the invitation
+    /// if it is the authenticated user.
+    #[derive(Deserialize)]
+    pub struct MaintainerRq {
+        pub username: String,
+    }
+}
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::{select_language, Localized};
+    use super::api::Cursor;
+
+    fn texts(languages: &[&str]) -> Vec<Localized> {
+        languages.iter().map(|x| Localized {
+            language: x.parse().unwrap(),
+            text: x.to_string(),
+        }).collect()
+    }
+
+    fn select(languages: &[&str], preferred: &[&str]) -> Vec<String> {
+        let mut texts = texts(languages);
+        let preferred = preferred.iter().map(|x| x.parse().unwrap()).collect::<Vec<_>>();
+
+        select_language(&mut texts, &preferred);
+        texts.into_iter().map(|x| x.text).collect()
+    }
+
+    #[test]
+    fn select_language_follows_preferences() {
+        assert_eq!(select(&["en", "ru"], &["ru", "en"]), vec!["ru"]);
+        assert_eq!(select(&["en", "ru"], &["de", "en"]), vec!["en"]);
+        assert_eq!(select(&["en", "en-US", "ru"], &["en-US"]), vec!["en-US"]);
+    }
+
+    #[test]
+    fn select_language_matches_primary_subtags() {
+        assert_eq!(select(&["ru", "en"], &["en-US"]), vec!["en"]);
+        assert_eq!(select(&["ru", "pt-BR"], &["pt"]), vec!["pt-BR"]);
+        // an exact match of a later preference doesn't beat a prefix match
+        assert_eq!(select(&["ru", "en"], &["en-GB", "ru"]), vec!["en"]);
+    }
+
+    #[test]
+    fn select_language_falls_back() {
+        assert_eq!(select(&["ru", "en"], &["de"]), vec!["en"]);
+        assert_eq!(select(&["ru", "uk"], &["de"]), vec!["ru"]);
+        assert_eq!(select(&["uk"], &["de"]), vec!["uk"]);
+        assert!(select(&[], &["de"]).is_empty());
+    }
+
+    fn cursor(name: &str) -> Cursor {
+        Cursor {
+            created: NaiveDate::from_ymd(2018, 4, 15).and_hms_nano(12, 30, 0, 123_456_000),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        for name in &["hel", "with:colon", "ünïcode", ""] {
+            let cursor = cursor(name);
+            assert_eq!(cursor.encode().parse::<Cursor>().unwrap(), cursor);
+        }
+    }
+
+    #[test]
+    fn cursor_rejects_bad_hex() {
+        let encoded = cursor("hel").encode();
+
+        assert!("zz".parse::<Cursor>().is_err());
+        assert!(format!("{}z", &encoded[1..]).parse::<Cursor>().is_err());
+        assert!(format!("{}0", encoded).parse::<Cursor>().is_err());
+        assert!(format!("{}ж", &encoded[2..]).parse::<Cursor>().is_err());
+        // not UTF-8
+        assert!("ff".parse::<Cursor>().is_err());
+    }
+
+    #[test]
+    fn cursor_rejects_truncated_input() {
+        let encoded = cursor("hel").encode();
+        let colon = encoded.find("3a").unwrap();
+
+        assert!("".parse::<Cursor>().is_err());
+        assert!(encoded[..colon].parse::<Cursor>().is_err());
+        assert!(encoded[..4].parse::<Cursor>().is_err());
+    }
+
+    #[test]
+    fn cursor_rejects_invalid_timestamps() {
+        let encode = |raw: &str| raw.bytes().map(|x| format!("{:02x}", x)).collect::<String>();
+
+        assert!(encode("1523795400:hel").parse::<Cursor>().is_err());
+        assert!(encode("1523795400.x:hel").parse::<Cursor>().is_err());
+        assert!(encode("-.0:hel").parse::<Cursor>().is_err());
+        assert!(encode("1523795400.2000000000:hel").parse::<Cursor>().is_err());
+        assert!(encode("99999999999999999.0:hel").parse::<Cursor>().is_err());
+        assert!(encode("1523795400.0:hel").parse::<Cursor>().is_ok());
+    }
+}
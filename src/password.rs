@@ -0,0 +1,31 @@
+use failure::{err_msg, Error};
+use ring::{digest, pbkdf2};
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// PBKDF2 iteration count. Changing it invalidates every stored password.
+const ITERATIONS: u32 = 100_000;
+
+pub type Hash = [u8; 64];
+pub type Salt = [u8; 64];
+
+pub fn generate_salt() -> Result<Salt, Error> {
+    let mut salt = [0u8; 64];
+
+    SystemRandom::new().fill(&mut salt)
+        .map_err(|_| err_msg("failed to generate a salt"))?;
+
+    Ok(salt)
+}
+
+/// Derives the password hash stored in `users.password` using
+/// PBKDF2-HMAC-SHA512.
+pub fn hash(password: &str, salt: &Salt) -> Hash {
+    let mut hash = [0u8; 64];
+    pbkdf2::derive(&digest::SHA512, ITERATIONS, salt, password.as_bytes(), &mut hash);
+    hash
+}
+
+/// Checks a password against a stored hash in constant time.
+pub fn verify(password: &str, salt: &[u8], hash: &[u8]) -> bool {
+    pbkdf2::verify(&digest::SHA512, ITERATIONS, salt, password.as_bytes(), hash).is_ok()
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
+use actix_web::error::{ResponseError, Result};
+use actix_web::http::{header, Method};
+use actix_web::middleware::{Middleware, Response, Started};
+
+use app::State;
+use config::config_groups::{Limit, RateLimitGroup};
+use error::RateLimited;
+
+/// Buckets are pruned every this many checks.
+const PRUNE_INTERVAL: usize = 1024;
+
+/// Routes and methods whose handlers verify credentials, see
+/// [`authenticating`]. Must match the handlers registered in `app::create`.
+const AUTHENTICATING: &[(&str, Method)] = &[
+    ("maintainers", Method::POST),
+    ("maintainer", Method::DELETE),
+];
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    limit: Limit,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.rate).min(f64::from(self.limit.burst));
+        self.updated = now;
+    }
+
+    fn refund(&mut self, now: Instant) {
+        self.refill(now);
+        self.tokens = (self.tokens + 1.0).min(f64::from(self.limit.burst));
+    }
+
+    fn is_full(&self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens + elapsed * self.limit.rate >= f64::from(self.limit.burst)
+    }
+}
+
+/// Whose bucket a request takes tokens from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Client {
+    User(i32),
+    Address(IpAddr),
+}
+
+/// Marks a request with credentials whose check waits until they have been
+/// verified, see [`authenticating`].
+struct Deferred(IpAddr);
+
+/// Token buckets per route and client, shared by all HTTP workers.
+pub struct RateLimiter {
+    config: RateLimitGroup,
+    buckets: Mutex<HashMap<(String, Client), Bucket>>,
+    checks: AtomicUsize,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitGroup) -> RateLimiter {
+        RateLimiter {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            checks: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes a token from the bucket of `client` for `route`. On failure
+    /// returns how long to wait until a token is available.
+    pub fn check(&self, route: &str, client: Client) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if self.checks.fetch_add(1, Ordering::Relaxed).is_multiple_of(PRUNE_INTERVAL) {
+            // a full bucket is the same as a missing one
+            buckets.retain(|_, bucket| !bucket.is_full(now));
+        }
+
+        let limit = self.config.limit(route);
+        let bucket = buckets.entry((route.to_string(), client)).or_insert_with(|| Bucket {
+            tokens: f64::from(limit.burst),
+            updated: now,
+            limit,
+        });
+
+        bucket.refill(now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / bucket.limit.rate))
+        }
+    }
+
+    /// Gives back a token taken by [`check`](#method.check).
+    pub fn refund(&self, route: &str, client: Client) {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(bucket) = buckets.get_mut(&(route.to_string(), client)) {
+            bucket.refund(Instant::now());
+        }
+    }
+
+    fn address(&self, req: &HttpRequest<State>) -> Option<IpAddr> {
+        if self.config.trust_proxy {
+            if let Some(remote) = req.connection_info().remote() {
+                return remote.parse::<IpAddr>().ok()
+                    .or_else(|| remote.parse::<SocketAddr>().ok().map(|x| x.ip()));
+            }
+        }
+
+        req.peer_addr().map(|x| x.ip())
+    }
+}
+
+fn route<S>(req: &HttpRequest<S>) -> &str {
+    match req.resource().name() {
+        "" => "unmatched",
+        name => name,
+    }
+}
+
+fn authenticates<S>(req: &HttpRequest<S>) -> bool {
+    let route = route(req);
+    AUTHENTICATING.iter().any(|&(name, ref method)| name == route && method == req.method())
+}
+
+fn rate_limited(wait: Duration) -> RateLimited {
+    RateLimited { retry_after: wait.as_secs() + u64::from(wait.subsec_nanos() > 0) }
+}
+
+/// Checks a request whose credentials are about to be verified by taking a
+/// token from the credential check bucket of the client address. The token
+/// is reserved before verifying so that concurrent guesses can't all pass.
+pub fn authenticating(req: &HttpRequest<State>) -> Result<(), RateLimited> {
+    let address = match req.extensions_ro().get::<Deferred>() {
+        Some(&Deferred(address)) => address,
+        None => return Ok(()),
+    };
+
+    req.state().limiter.check(RateLimitGroup::CREDENTIALS, Client::Address(address))
+        .map_err(rate_limited)
+}
+
+/// Takes a token for a request once its credentials have been checked. If
+/// they were valid, the credential check token is given back and the route
+/// token is taken from the bucket of `user`; otherwise both stay charged to
+/// the client address.
+pub fn authenticated(req: &HttpRequest<State>, user: Option<i32>) -> Result<(), RateLimited> {
+    let address = match req.clone().extensions().remove::<Deferred>() {
+        Some(Deferred(address)) => address,
+        None => return Ok(()),
+    };
+
+    let limiter = &req.state().limiter;
+    let client = match user {
+        Some(id) => {
+            limiter.refund(RateLimitGroup::CREDENTIALS, Client::Address(address));
+            Client::User(id)
+        }
+        None => Client::Address(address),
+    };
+
+    limiter.check(route(req), client).map_err(rate_limited)
+}
+
+/// Rejects requests with 429 once a client runs out of tokens for a route.
+///
+/// Clients are users for requests with valid credentials and addresses
+/// otherwise. Requests with credentials to routes that verify them are
+/// checked by the handler; if it doesn't get that far, they are counted
+/// against the address once the handler is done. Every other request is
+/// checked against its address up front.
+pub struct RateLimit;
+
+impl Middleware<State> for RateLimit {
+    fn start(&self, req: &mut HttpRequest<State>) -> Result<Started> {
+        let limiter = req.state().limiter.clone();
+
+        if !limiter.config.enabled {
+            return Ok(Started::Done);
+        }
+
+        let address = match limiter.address(req) {
+            Some(address) => address,
+            None => return Ok(Started::Done),
+        };
+
+        if authenticates(req) && req.headers().contains_key(header::AUTHORIZATION) {
+            req.extensions().insert(Deferred(address));
+            return Ok(Started::Done);
+        }
+
+        match limiter.check(route(req), Client::Address(address)) {
+            Ok(()) => Ok(Started::Done),
+            Err(wait) => Ok(Started::Response(rate_limited(wait).error_response())),
+        }
+    }
+
+    fn response(&self, req: &mut HttpRequest<State>, resp: HttpResponse) -> Result<Response> {
+        // the handler didn't verify the credentials
+        match authenticated(req, None) {
+            Ok(()) => Ok(Response::Done(resp)),
+            Err(err) => Ok(Response::Done(err.error_response())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credential_checks_are_reserved_and_refunded() {
+        let limiter = RateLimiter::new(RateLimitGroup::default());
+        let client = Client::Address("127.0.0.1".parse().unwrap());
+
+        for _ in 0..5 {
+            assert!(limiter.check(RateLimitGroup::CREDENTIALS, client).is_ok());
+        }
+
+        assert!(limiter.check(RateLimitGroup::CREDENTIALS, client).is_err());
+
+        limiter.refund(RateLimitGroup::CREDENTIALS, client);
+        assert!(limiter.check(RateLimitGroup::CREDENTIALS, client).is_ok());
+        assert!(limiter.check("maintainers", client).is_ok());
+    }
+}
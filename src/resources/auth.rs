@@ -0,0 +1,80 @@
+use actix_web::{AsyncResponder, Error as ActixError, HttpMessage, HttpRequest, HttpResponse};
+use actix_web::http::header;
+use base64;
+use futures::{future, Future, IntoFuture};
+
+use ::app::State;
+use ::db::messages::{self, Identity};
+use ::db::models::types::UserGroup;
+use ::error::AuthError;
+use ::logging::RequestContext;
+use ::ratelimit;
+use super::{Db, ResponseFuture};
+use super::error::db_error;
+
+/// Authenticates a request with the HTTP Basic credentials of a registered
+/// user and continues with `f`. Banned users are rejected.
+///
+/// The request is rate limited per user if the credentials are valid and
+/// per client address otherwise. The user is recorded in the access log.
+pub fn authenticated<F, R>(req: &HttpRequest<State>, f: F) -> ResponseFuture
+    where F: FnOnce(Identity) -> R + 'static,
+          R: IntoFuture<Item=HttpResponse, Error=ActixError> + 'static,
+{
+    let (username, password) = match credentials(req) {
+        Ok(credentials) => credentials,
+        Err(e) => return Box::new(future::err(e.into())),
+    };
+
+    if let Err(e) = ratelimit::authenticating(req) {
+        return Box::new(future::err(e.into()));
+    }
+
+    let req = req.clone();
+
+    Db::new(&req).send(messages::Authenticate { username, password })
+        .from_err::<ActixError>()
+        .and_then(move |res| {
+            let user = res.map_err(db_error)?;
+
+            if let (Some(ctx), Some(user)) = (RequestContext::of(&req), user.as_ref()) {
+                ctx.set_user(user.id, &user.username);
+            }
+
+            ratelimit::authenticated(&req, user.as_ref().map(|x| x.id))?;
+
+            match user {
+                Some(user) => match user.group {
+                    UserGroup::Banned => Err(AuthError::Banned { username: user.username }.into()),
+                    _ => Ok(user),
+                },
+                None => Err(AuthError::InvalidCredentials.into()),
+            }
+        })
+        .and_then(f)
+        .responder()
+}
+
+fn credentials(req: &HttpRequest<State>) -> Result<(String, String), AuthError> {
+    let value = req.headers().get(header::AUTHORIZATION)
+        .ok_or(AuthError::Required)?
+        .to_str()
+        .map_err(|_| AuthError::InvalidCredentials)?;
+
+    let mut parts = value.splitn(2, ' ');
+
+    match parts.next() {
+        Some(scheme) if scheme.eq_ignore_ascii_case("basic") => {}
+        _ => return Err(AuthError::Required),
+    }
+
+    let decoded = parts.next()
+        .and_then(|x| base64::decode(x.trim()).ok())
+        .and_then(|x| String::from_utf8(x).ok())
+        .ok_or(AuthError::InvalidCredentials)?;
+
+    match decoded.split_once(':') {
+        Some((username, password)) => Ok((username.to_string(), password.to_string())),
+        None => Err(AuthError::InvalidCredentials),
+    }
+}
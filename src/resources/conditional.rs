@@ -0,0 +1,157 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
+use actix_web::http::header::{self, EntityTag, ETag, Header, HttpDate, IfModifiedSince,
+                              IfNoneMatch, LastModified};
+use chrono::{self, NaiveDateTime, Utc};
+use failure::Error;
+use ring::digest;
+use serde::Serialize;
+use serde_json;
+
+/// Serializes `body` as JSON with validators, answering 304 Not Modified if
+/// the client's copy is still current.
+///
+/// The entity tag is a digest of the body, so it also changes when derived
+/// values such as likes do. `modified` is when anything in the body last
+/// changed; `If-Modified-Since` is only consulted without `If-None-Match`, as
+/// RFC 7232 requires.
+pub fn respond<S, T>(req: &HttpRequest<S>, body: &T, modified: Option<NaiveDateTime>)
+    -> Result<HttpResponse, Error>
+    where T: Serialize
+{
+    let json = serde_json::to_vec(body)?;
+    let etag = entity_tag(&json);
+    let modified = modified.and_then(last_modified);
+
+    // a missing If-None-Match parses as an empty list, so check for it first
+    let not_modified = if req.headers().contains_key(header::IF_NONE_MATCH) {
+        match IfNoneMatch::parse(req) {
+            Ok(IfNoneMatch::Any) => true,
+            Ok(IfNoneMatch::Items(ref tags)) => tags.iter().any(|x| x.weak_eq(&etag)),
+            Err(_) => false,
+        }
+    } else {
+        match (IfModifiedSince::parse(req), modified) {
+            (Ok(IfModifiedSince(since)), Some(modified)) => modified <= since,
+            _ => false,
+        }
+    };
+
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+
+    response
+        .header(header::VARY, "Accept-Language")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .set(ETag(etag));
+
+    if let Some(modified) = modified {
+        response.set(LastModified(modified));
+    }
+
+    if not_modified {
+        Ok(response.finish())
+    } else {
+        Ok(response.content_type("application/json").body(json))
+    }
+}
+
+/// Converts a modification time to an HTTP date, which has second
+/// precision. A change in the same second as `modified` would go unnoticed,
+/// so nothing is returned until that second is over (RFC 7232, section
+/// 2.2.2). Timestamps are stored in UTC by a database sharing our clock.
+fn last_modified(modified: NaiveDateTime) -> Option<HttpDate> {
+    if Utc::now().naive_utc().signed_duration_since(modified) < chrono::Duration::seconds(1) {
+        return None;
+    }
+
+    Some((UNIX_EPOCH + Duration::from_secs(modified.timestamp().max(0) as u64)).into())
+}
+
+fn entity_tag(body: &[u8]) -> EntityTag {
+    let hash = digest::digest(&digest::SHA256, body);
+    let hex = hash.as_ref()[..16].iter()
+        .map(|x| format!("{:02x}", x))
+        .collect();
+
+    EntityTag::strong(hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn modified() -> NaiveDateTime {
+        NaiveDate::from_ymd(2018, 4, 10).and_hms_milli(12, 0, 0, 500)
+    }
+
+    #[test]
+    fn last_modified_is_sent() {
+        let req = TestRequest::default().finish();
+        let resp = respond(&req, &"body", Some(modified())).unwrap();
+        assert_eq!(resp.headers().get(header::LAST_MODIFIED).unwrap(),
+                   "Tue, 10 Apr 2018 12:00:00 GMT");
+
+        let resp = respond(&req, &"body", None).unwrap();
+        assert!(!resp.headers().contains_key(header::LAST_MODIFIED));
+    }
+
+    #[test]
+    fn last_modified_waits_for_the_second_to_pass() {
+        let req = TestRequest::with_header(header::IF_MODIFIED_SINCE,
+                                           "Fri, 01 Jan 2100 00:00:00 GMT").finish();
+        let resp = respond(&req, &"body", Some(Utc::now().naive_utc())).unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(!resp.headers().contains_key(header::LAST_MODIFIED));
+    }
+
+    #[test]
+    fn if_modified_since_alone() {
+        let req = TestRequest::with_header(header::IF_MODIFIED_SINCE,
+                                           "Tue, 10 Apr 2018 12:00:00 GMT").finish();
+        let resp = respond(&req, &"body", Some(modified())).unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert!(resp.headers().contains_key(header::LAST_MODIFIED));
+
+        let req = TestRequest::with_header(header::IF_MODIFIED_SINCE,
+                                           "Tue, 10 Apr 2018 11:59:59 GMT").finish();
+        let resp = respond(&req, &"body", Some(modified())).unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn if_none_match() {
+        let etag = entity_tag(b"\"body\"");
+        let req = TestRequest::with_header(header::IF_NONE_MATCH, format!("{}", etag)).finish();
+        let resp = respond(&req, &"body", None).unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+        let req = TestRequest::with_header(header::IF_NONE_MATCH, "\"other\"").finish();
+        let resp = respond(&req, &"body", None).unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence() {
+        let etag = entity_tag(b"\"body\"");
+        let req = TestRequest::with_header(header::IF_NONE_MATCH, format!("{}", etag))
+            .header(header::IF_MODIFIED_SINCE, "Tue, 10 Apr 2018 11:00:00 GMT")
+            .finish();
+        let resp = respond(&req, &"body", Some(modified())).unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+        let req = TestRequest::with_header(header::IF_NONE_MATCH, "\"other\"")
+            .header(header::IF_MODIFIED_SINCE, "Tue, 10 Apr 2018 13:00:00 GMT")
+            .finish();
+        let resp = respond(&req, &"body", Some(modified())).unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
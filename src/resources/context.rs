@@ -0,0 +1,48 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix::{Addr, Handler, Message, Syn};
+use actix::dev::Request;
+use actix_web::{Error as ActixError, FromRequest, HttpRequest};
+use futures::future::{self, FutureResult};
+
+use ::app::State;
+use ::db::{self, DbExecutor, Traced};
+use ::logging::RequestContext;
+
+/// Database executors bound to the request being served, so that messages
+/// sent through it are logged with the request id.
+pub struct Db {
+    addr: Addr<Syn, DbExecutor>,
+    ctx: Option<Arc<RequestContext>>,
+    timeout: Duration,
+}
+
+impl Db {
+    pub fn new(req: &HttpRequest<State>) -> Db {
+        Db {
+            addr: req.state().db.clone(),
+            ctx: RequestContext::of(req),
+            timeout: Duration::from_millis(req.state().config.http.request_timeout),
+        }
+    }
+
+    /// Sends a message, giving up after `http.request_timeout`.
+    pub fn send<M>(&self, msg: M) -> Request<Syn, DbExecutor, Traced<M>>
+        where M: Message + Send + 'static,
+              Traced<M>: Message,
+              <Traced<M> as Message>::Result: Send,
+              DbExecutor: Handler<Traced<M>>,
+    {
+        db::send(&self.addr, self.ctx.clone(), msg).timeout(self.timeout)
+    }
+}
+
+impl FromRequest<State> for Db {
+    type Config = ();
+    type Result = FutureResult<Self, ActixError>;
+
+    fn from_request(req: &HttpRequest<State>, _: &Self::Config) -> Self::Result {
+        future::ok(Db::new(req))
+    }
+}
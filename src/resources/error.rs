@@ -1,3 +1,77 @@
-use actix_web::error::ResponseError;
+use actix_web::{Error as ActixError, HttpResponse};
+use actix_web::error::{ErrorBadRequest, ErrorNotFound, ResponseError};
+use actix_web::http::{header, StatusCode};
+use diesel;
+use diesel::result::DatabaseErrorKind;
+use failure;
 
-impl ResponseError for ::error::ParseError {}
\ No newline at end of file
+use ::error::{AuthError, MaintainerError, ParseError, RateLimited, ValidationError};
+
+impl ResponseError for ParseError {}
+
+impl ResponseError for ValidationError {
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::BadRequest().body(self.to_string())
+    }
+}
+
+impl ResponseError for AuthError {
+    fn error_response(&self) -> HttpResponse {
+        match *self {
+            AuthError::Banned { .. } => HttpResponse::Forbidden().body(self.to_string()),
+            _ => HttpResponse::Unauthorized()
+                .header(header::WWW_AUTHENTICATE, "Basic realm=\"hel\"")
+                .body(self.to_string()),
+        }
+    }
+}
+
+impl ResponseError for RateLimited {
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(StatusCode::TOO_MANY_REQUESTS)
+            .header(header::RETRY_AFTER, self.retry_after.to_string())
+            .body("too many requests")
+    }
+}
+
+impl ResponseError for MaintainerError {
+    fn error_response(&self) -> HttpResponse {
+        let mut response = match *self {
+            MaintainerError::NotMaintainer { .. } => HttpResponse::Forbidden(),
+            MaintainerError::NotListed { .. } |
+            MaintainerError::NoInvitation { .. } => HttpResponse::NotFound(),
+            MaintainerError::AlreadyMaintainer { .. } |
+            MaintainerError::LastMaintainer { .. } => HttpResponse::Conflict(),
+        };
+
+        response.body(self.to_string())
+    }
+}
+
+/// Converts an error returned by a database message, keeping the status of
+/// errors caused by the request instead of answering 500.
+pub fn db_error(err: failure::Error) -> ActixError {
+    let err = match err.downcast::<ValidationError>() {
+        Ok(err) => return err.into(),
+        Err(err) => err,
+    };
+    let err = match err.downcast::<MaintainerError>() {
+        Ok(err) => return err.into(),
+        Err(err) => err,
+    };
+    let err = match err.downcast::<AuthError>() {
+        Ok(err) => return err.into(),
+        Err(err) => err,
+    };
+
+    match err.downcast::<diesel::result::Error>() {
+        Ok(diesel::result::Error::NotFound) => ErrorNotFound("not found"),
+        // the request names packages that don't exist or repeats a key
+        Ok(diesel::result::Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, info)) |
+        Ok(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, info)) => {
+            ErrorBadRequest(info.message().to_string())
+        }
+        Ok(err) => failure::Error::from(err).into(),
+        Err(err) => err.into(),
+    }
+}
@@ -0,0 +1,87 @@
+use std::cmp::Reverse;
+
+use actix_web::HttpRequest;
+use actix_web::http::header::{self, AcceptLanguage, Header};
+
+use ::db::models::types::Language;
+
+/// Languages the client asked for, most preferred first.
+///
+/// `None` means the client wants every localized text. The `lang` query
+/// parameter (a comma-separated list, or `all`) takes precedence over the
+/// `Accept-Language` header.
+pub struct Languages(pub Option<Vec<Language>>);
+
+impl Languages {
+    pub fn negotiate<S>(req: &HttpRequest<S>) -> Languages {
+        if let Some(lang) = req.query().get("lang") {
+            if lang == "all" {
+                return Languages(None);
+            }
+
+            return Languages::from_list(lang.split(',').filter_map(|x| x.parse().ok()).collect());
+        }
+
+        match AcceptLanguage::parse(req) {
+            Ok(AcceptLanguage(mut items)) => {
+                // sort is stable, so equally ranked languages keep header order
+                items.sort_by_key(|x| Reverse(x.quality));
+
+                Languages::from_list(items.into_iter()
+                    .filter(|x| x.quality > header::q(0))
+                    .filter_map(|x| x.item.to_string().parse().ok())
+                    .collect())
+            }
+            Err(_) => Languages(None),
+        }
+    }
+
+    fn from_list(list: Vec<Language>) -> Languages {
+        if list.is_empty() {
+            Languages(None)
+        } else {
+            Languages(Some(list))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    fn negotiate(req: TestRequest<()>) -> Option<Vec<String>> {
+        Languages::negotiate(&req.finish()).0
+            .map(|x| x.iter().map(|x| x.to_string()).collect())
+    }
+
+    fn accept(value: &str) -> TestRequest<()> {
+        TestRequest::with_header("Accept-Language", value)
+    }
+
+    #[test]
+    fn accept_language_by_quality() {
+        assert_eq!(negotiate(accept("en;q=0.5, ru, de;q=0.8")),
+                   Some(vec!["ru".into(), "de".into(), "en".into()]));
+        assert_eq!(negotiate(accept("en-US, en;q=0.9")),
+                   Some(vec!["en-US".into(), "en".into()]));
+        assert_eq!(negotiate(accept("ru, de;q=0")), Some(vec!["ru".into()]));
+    }
+
+    #[test]
+    fn accept_language_wildcard_means_all() {
+        assert_eq!(negotiate(accept("*")), None);
+        assert_eq!(negotiate(accept("ru, *;q=0.5")), Some(vec!["ru".into()]));
+        assert_eq!(negotiate(TestRequest::default()), None);
+    }
+
+    #[test]
+    fn lang_parameter_overrides_header() {
+        assert_eq!(negotiate(accept("en").uri("/?lang=ru,uk")),
+                   Some(vec!["ru".into(), "uk".into()]));
+        assert_eq!(negotiate(accept("en").uri("/?lang=all")), None);
+        assert_eq!(negotiate(accept("en").uri("/?lang=rus")), Some(vec!["ru".into()]));
+        assert_eq!(negotiate(accept("en").uri("/?lang=!!")), None);
+    }
+}
@@ -4,50 +4,285 @@ use actix_web::{
     HttpRequest,
     HttpResponse,
     HttpMessage,
+    Json,
     Path as PathExtractor,
     State as StateExtractor,
 };
-use futures::Future;
+use actix_web::http::header;
+use std::time::Duration;
+
+use futures::{future, Future};
 
 use ::app::State;
 use ::db::messages;
+use ::metrics;
 use ::models;
+use ::models::Localize;
+
+mod auth;
+mod conditional;
+mod context;
+mod error;
+mod language;
+#[cfg(test)]
+mod tests;
+
+pub use self::context::Db;
+pub use self::language::Languages;
 
 type ResponseFuture = Box<Future<Item=HttpResponse, Error=ActixError>>;
 
-pub fn index(_req: HttpRequest<State>) -> &'static str {
-    "Hello, World!"
+pub fn health(_req: HttpRequest<State>) -> HttpResponse {
+    HttpResponse::Ok().json(models::service::Status {
+        status: "ok".to_string(),
+    })
+}
+
+pub fn ready(state: StateExtractor<State>, db: Db) -> ResponseFuture {
+    let timeout = Duration::from_millis(state.config.database.ready_timeout);
+
+    db.send(messages::Ping)
+        .timeout(timeout)
+        .then(|res| {
+            let (mut response, status) = match res {
+                Ok(Ok(())) => (HttpResponse::Ok(), "ready"),
+                _ => (HttpResponse::ServiceUnavailable(), "unavailable"),
+            };
+
+            Ok(response.json(models::service::Status {
+                status: status.to_string(),
+            }))
+        })
+        .responder()
+}
+
+pub fn version(db: Db) -> ResponseFuture {
+    db.send(messages::GetSchemaVersion)
+        .from_err::<ActixError>()
+        .and_then(|res| {
+            Ok(HttpResponse::Ok().json(models::service::Version {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                schema_version: res?,
+            }))
+        })
+        .from_err()
+        .responder()
 }
 
 pub fn list_packages(req: HttpRequest<State>) -> ResponseFuture {
-    let db = req.state().db.clone();
     let page_limit = req.state().config.http.pagination_limit;
+    let body_limit = req.state().config.http.max_body_size;
 
-    req.json()
+    req.clone().json()
+        .limit(body_limit)
         .from_err::<ActixError>()
         .and_then(move |page: models::api::PaginationRq| {
             let page = page.validate(page_limit);
 
-            db.send(messages::GetPackages {
-                page: page.page,
-                limit: page.limit,
-            }).from_err()
+            match page.cursor {
+                Some(ref cursor) => list_packages_after(req, cursor, page.limit),
+                None => list_packages_page(req, page.page, page.limit),
+            }
         })
-        .and_then(|res| {
-            Ok(HttpResponse::Ok().json(res?))
+        .responder()
+}
+
+fn list_packages_page(req: HttpRequest<State>, page: u32, limit: u32) -> ResponseFuture {
+    let languages = Languages::negotiate(&req);
+
+    Db::new(&req).send(messages::GetPackages {
+        page,
+        limit,
+    })
+        .from_err::<ActixError>()
+        .and_then(move |res| {
+            let (mut packages, updated) = res?;
+            localize_packages(&languages, &mut packages);
+
+            Ok(conditional::respond(&req, &packages, Some(updated))?)
         })
         .from_err()
         .responder()
 }
 
-pub fn get_package(state: StateExtractor<State>, path: PathExtractor<models::api::Name>)
+fn list_packages_after(req: HttpRequest<State>, cursor: &str, limit: u32) -> ResponseFuture {
+    let cursor = match cursor {
+        "" => None,
+        cursor => match cursor.parse::<models::api::Cursor>() {
+            Ok(cursor) => Some(cursor),
+            Err(e) => return Box::new(future::err(e.into())),
+        },
+    };
+    let languages = Languages::negotiate(&req);
+
+    Db::new(&req).send(messages::GetPackagesAfter {
+        cursor,
+        limit,
+    })
+        .from_err::<ActixError>()
+        .and_then(move |res| {
+            let (mut packages, next, updated) = res?;
+            localize_packages(&languages, &mut packages);
+
+            let page = models::api::PackagePage {
+                packages,
+                next: next.map(|x| x.encode()),
+            };
+
+            Ok(conditional::respond(&req, &page, Some(updated))?)
+        })
+        .from_err()
+        .responder()
+}
+
+fn localize_packages(languages: &Languages, packages: &mut [models::package::Short]) {
+    if let Some(ref preferred) = languages.0 {
+        for package in packages.iter_mut() {
+            package.localize(preferred);
+        }
+    }
+}
+
+pub fn get_package(req: HttpRequest<State>, path: PathExtractor<models::api::Name>)
     -> ResponseFuture
 {
-    state.db.send(messages::GetPackage(path.name.clone()))
+    let languages = Languages::negotiate(&req);
+
+    Db::new(&req).send(messages::GetPackage(path.name.clone()))
+        .from_err::<ActixError>()
+        .and_then(move |res| {
+            let mut package = res.map_err(error::db_error)?;
+
+            if package.name != path.name {
+                return canonical_redirect(&req, &package.name);
+            }
+
+            if let Some(ref preferred) = languages.0 {
+                package.localize(preferred);
+            }
+
+            let updated = package.updated;
+
+            Ok(conditional::respond(&req, &package, Some(updated))?)
+        })
+        .from_err()
+        .responder()
+}
+
+/// Sends clients that spelled a package name in another case to its canonical
+/// URL, keeping the query string.
+fn canonical_redirect(req: &HttpRequest<State>, name: &str) -> Result<HttpResponse, ActixError> {
+    let mut location = req.url_for("get_package", [name])?;
+
+    if !req.query_string().is_empty() {
+        location.set_query(Some(req.query_string()));
+    }
+
+    Ok(HttpResponse::PermanentRedirect()
+        .header(header::LOCATION, location.as_str())
+        .finish())
+}
+
+pub fn get_conflicts(db: Db, path: PathExtractor<models::api::NameVersion>) -> ResponseFuture {
+    db.send(messages::GetConflicts {
+        package: path.name.clone(),
+        version: path.version.clone(),
+    })
         .from_err::<ActixError>()
         .and_then(|res| {
-            Ok(HttpResponse::Ok().json(res?))
+            Ok(HttpResponse::Ok().json(res.map_err(error::db_error)?))
         })
         .from_err()
         .responder()
-}
\ No newline at end of file
+}
+
+pub fn metrics(state: StateExtractor<State>, db: Db) -> ResponseFuture {
+    let timeout = Duration::from_millis(state.config.database.ready_timeout);
+
+    let counted = if metrics::counts_due() {
+        future::Either::A(db.send(messages::GetCounts)
+            .timeout(timeout)
+            .then(|res| {
+                // the remaining metrics are still useful while the database is
+                // down
+                if let Ok(Ok(counts)) = res {
+                    metrics::set_counts(counts.packages, counts.users);
+                }
+
+                Ok(())
+            }))
+    } else {
+        future::Either::B(future::ok(()))
+    };
+
+    counted
+        .map(|()| {
+            HttpResponse::Ok()
+                .content_type(metrics::CONTENT_TYPE)
+                .body(metrics::render())
+        })
+        .responder()
+}
+
+pub fn get_maintainers(db: Db, path: PathExtractor<models::api::Name>) -> ResponseFuture {
+    db.send(messages::GetMaintainers(path.name.clone()))
+        .from_err::<ActixError>()
+        .and_then(|res| {
+            Ok(HttpResponse::Ok().json(res.map_err(error::db_error)?))
+        })
+        .responder()
+}
+
+/// Invites a user to maintain the package, or accepts an invitation if the
+/// user is the one making the request.
+pub fn add_maintainer(req: HttpRequest<State>, path: PathExtractor<models::api::Name>,
+                      body: Json<models::api::MaintainerRq>) -> ResponseFuture {
+    let db = Db::new(&req);
+
+    auth::authenticated(&req, move |identity| {
+        let package = path.name.clone();
+        let accepting = body.username.eq_ignore_ascii_case(&identity.username);
+
+        let sent = if accepting {
+            future::Either::A(db.send(messages::AcceptInvitation {
+                package,
+                by: identity,
+            }))
+        } else {
+            future::Either::B(db.send(messages::InviteMaintainer {
+                package,
+                username: body.username.clone(),
+                by: identity,
+            }))
+        };
+
+        sent.from_err::<ActixError>().and_then(move |res| {
+            let list = res.map_err(error::db_error)?;
+
+            if accepting {
+                Ok(HttpResponse::Ok().json(list))
+            } else {
+                Ok(HttpResponse::Accepted().json(list))
+            }
+        })
+    })
+}
+
+/// Removes a maintainer or withdraws an invitation.
+pub fn remove_maintainer(req: HttpRequest<State>, path: PathExtractor<models::api::NameUsername>)
+    -> ResponseFuture
+{
+    let db = Db::new(&req);
+
+    auth::authenticated(&req, move |identity| {
+        db.send(messages::RemoveMaintainer {
+            package: path.name.clone(),
+            username: path.username.clone(),
+            by: identity,
+        })
+            .from_err::<ActixError>()
+            .and_then(|res| {
+                Ok(HttpResponse::Ok().json(res.map_err(error::db_error)?))
+            })
+    })
+}
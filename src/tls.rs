@@ -0,0 +1,125 @@
+use std::net::ToSocketAddrs;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+use actix::{Recipient, Syn, System};
+use actix_web::{App, HttpRequest, HttpResponse};
+use actix_web::http::header;
+use actix_web::server::{self, HttpServer, IntoHttpHandler, StopServer};
+use failure::{err_msg, Error};
+
+#[cfg(feature = "tls")]
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+
+use config::config_groups::HttpGroup;
+
+/// Starts serving HTTPS with the configured certificate chain and key.
+#[cfg(feature = "tls")]
+pub fn start<H: IntoHttpHandler + 'static>(server: HttpServer<H>, config: &HttpGroup)
+    -> Result<(), Error>
+{
+    let (cert, key) = match (config.tls_cert.as_ref(), config.tls_key.as_ref()) {
+        (Some(cert), Some(key)) => (cert, key),
+        _ => return Err(err_msg("http.tls_cert and http.tls_key must both be set")),
+    };
+
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    builder.set_private_key_file(key, SslFiletype::PEM)?;
+    builder.set_certificate_chain_file(cert)?;
+    builder.check_private_key()?;
+
+    server.start_ssl(builder)?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "tls"))]
+pub fn start<H: IntoHttpHandler + 'static>(_server: HttpServer<H>, _config: &HttpGroup)
+    -> Result<(), Error>
+{
+    Err(err_msg("built without TLS support, rebuild with `--features tls`"))
+}
+
+/// A running redirect listener, see [`start_redirect`].
+pub struct Redirect {
+    server: Recipient<Syn, StopServer>,
+    thread: JoinHandle<()>,
+}
+
+impl Redirect {
+    /// Stops the listener and waits for its thread to exit.
+    pub fn stop(self) {
+        let _ = self.server.do_send(StopServer { graceful: false });
+        let _ = self.thread.join();
+    }
+}
+
+/// Starts a plain HTTP listener on `http.redirect_address` that permanently
+/// redirects every request to the HTTPS server.
+///
+/// The listener runs in its own system and ignores signals, so it keeps
+/// answering until [`Redirect::stop`] is called after the HTTPS server has
+/// shut down.
+pub fn start_redirect(config: &HttpGroup) -> Result<Option<Redirect>, Error> {
+    let address = match config.redirect_address {
+        Some(ref address) => address.clone(),
+        None => return Ok(None),
+    };
+
+    let port = config.bind_address.to_socket_addrs()?
+        .next()
+        .map(|x| x.port())
+        .ok_or_else(|| err_msg("http.bind_address does not resolve"))?;
+
+    let (tx, rx) = mpsc::channel();
+
+    let thread = thread::spawn(move || {
+        let sys = System::new("redirect");
+
+        let server = server::new(move || App::with_state(port).default_resource(|r| r.f(redirect)))
+            .disable_signals()
+            .system_exit()
+            .bind(&address);
+
+        match server {
+            Ok(server) => {
+                let _ = tx.send(Ok(server.start().recipient()));
+                sys.run();
+            }
+            Err(e) => {
+                let _ = tx.send(Err(e));
+            }
+        }
+    });
+
+    match rx.recv() {
+        Ok(Ok(server)) => Ok(Some(Redirect { server, thread })),
+        Ok(Err(e)) => Err(e.into()),
+        Err(_) => Err(err_msg("redirect listener failed to start")),
+    }
+}
+
+fn redirect(req: HttpRequest<u16>) -> HttpResponse {
+    let port = *req.state();
+    let host = strip_port(req.connection_info().host());
+    let location = match port {
+        443 => format!("https://{}{}", host, req.uri()),
+        _ => format!("https://{}:{}{}", host, port, req.uri()),
+    };
+
+    HttpResponse::PermanentRedirect()
+        .header(header::LOCATION, location)
+        .finish()
+}
+
+fn strip_port(host: &str) -> &str {
+    // an IPv6 literal without a port ends with the closing bracket
+    if host.ends_with(']') {
+        return host;
+    }
+
+    match host.rfind(':') {
+        Some(i) => &host[..i],
+        None => host,
+    }
+}
@@ -0,0 +1,414 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use ::config::config_groups::NamesGroup;
+use ::db::models::types::NodeType;
+use ::error::{ContentError, ValidationError};
+use ::models::ContentNode;
+
+pub const MIN_NAME_LENGTH: usize = 2;
+
+/// Names up to this length differ too often by a single edit to be compared
+/// by distance.
+const SHORT_NAME_LENGTH: usize = 3;
+
+/// Normalizes a content node path to the `dir/subdir/file` form.
+///
+/// Empty and `.` segments are dropped; absolute paths, `..` segments,
+/// backslashes and control characters are rejected, since installers write
+/// these paths to disk relative to the installation root.
+pub fn normalize_path(path: &str) -> Result<String, ContentError> {
+    if path.chars().any(|c| c == '\\' || c.is_control()) {
+        return Err(ContentError::ForbiddenCharacter { path: path.to_string() });
+    }
+
+    if path.starts_with('/') {
+        return Err(ContentError::AbsolutePath { path: path.to_string() });
+    }
+
+    let mut segments: Vec<&str> = Vec::new();
+
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => return Err(ContentError::ParentReference { path: path.to_string() }),
+            _ => segments.push(segment),
+        }
+    }
+
+    if segments.is_empty() {
+        return Err(ContentError::EmptyPath { path: path.to_string() });
+    }
+
+    Ok(segments.join("/"))
+}
+
+/// Validates the contents of a single version.
+///
+/// Returns the normalized nodes sorted by path, with undeclared parent
+/// directories added, or every problem found, also sorted by path.
+pub fn contents(nodes: Vec<ContentNode>) -> Result<Vec<ContentNode>, Vec<ContentError>> {
+    let mut errors: Vec<ContentError> = Vec::new();
+    let mut declared: HashMap<String, NodeType> = HashMap::with_capacity(nodes.len());
+
+    for node in nodes {
+        match normalize_path(&node.path) {
+            Ok(path) => {
+                match declared.entry(path) {
+                    Entry::Occupied(_) => {
+                        errors.push(ContentError::DuplicatePath { path: node.path });
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(node.node_type);
+                    }
+                }
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    let mut derived: Vec<String> = Vec::new();
+
+    for path in declared.keys() {
+        let mut parent_end = path.find('/');
+
+        while let Some(end) = parent_end {
+            let parent = &path[..end];
+
+            match declared.get(parent) {
+                Some(&NodeType::File) => {
+                    errors.push(ContentError::ParentIsFile {
+                        path: path.clone(),
+                        parent: parent.to_string(),
+                    });
+                    break;
+                }
+                Some(&NodeType::Directory) => {}
+                None => derived.push(parent.to_string()),
+            }
+
+            parent_end = path[end + 1..].find('/').map(|x| x + end + 1);
+        }
+    }
+
+    if !errors.is_empty() {
+        // parents are checked in hash order
+        errors.sort_by(|a, b| a.path().cmp(b.path()));
+        return Err(errors);
+    }
+
+    for path in derived {
+        declared.insert(path, NodeType::Directory);
+    }
+
+    let mut result: Vec<ContentNode> = declared
+        .into_iter()
+        .map(|(path, node_type)| ContentNode { node_type, path })
+        .collect();
+    result.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(result)
+}
+
+/// Checks the form of a package name and that it isn't reserved.
+///
+/// Uniqueness depends on existing packages and is checked by the database
+/// messages that take names, backed by a unique index on `lower(name)`.
+pub fn package_name(name: &str, config: &NamesGroup) -> Result<(), ValidationError> {
+    if name.len() < MIN_NAME_LENGTH || name.len() > config.max_length {
+        return Err(ValidationError::NameLength {
+            name: name.to_string(),
+            min: MIN_NAME_LENGTH,
+            max: config.max_length,
+        });
+    }
+
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.ends_with(|c: char| c.is_ascii_alphanumeric())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !valid {
+        return Err(ValidationError::NameCharacters { name: name.to_string() });
+    }
+
+    if config.reserved.iter().any(|x| x.eq_ignore_ascii_case(name)) {
+        return Err(ValidationError::ReservedName { name: name.to_string() });
+    }
+
+    Ok(())
+}
+
+/// Finds the existing name `name` is most likely to be mistaken for.
+///
+/// Names are compared with case, separators and look-alike digits ignored,
+/// and then, unless either is short, by edit distance of at most
+/// `max_distance`. Exact case-insensitive matches are not reported, since
+/// they are a uniqueness violation rather than a similarity.
+pub fn similar_name<'a, I>(name: &str, existing: I, max_distance: usize) -> Option<&'a str>
+    where I: IntoIterator<Item = &'a str>
+{
+    if max_distance == 0 {
+        return None;
+    }
+
+    let skeleton = name_skeleton(name);
+
+    existing.into_iter()
+        .filter(|x| !x.eq_ignore_ascii_case(name))
+        .filter_map(|x| {
+            let other = name_skeleton(x);
+
+            if other == skeleton {
+                return Some((0, x));
+            }
+
+            if other.len().min(skeleton.len()) <= SHORT_NAME_LENGTH {
+                return None;
+            }
+
+            match edit_distance(&other, &skeleton) {
+                distance if distance <= max_distance => Some((distance, x)),
+                _ => None,
+            }
+        })
+        .min_by_key(|x| x.0)
+        .map(|x| x.1)
+}
+
+/// The form of a name [`similar_name`] compares: lowercase, without
+/// separators, with `0` and `1` read as `o` and `l`.
+pub fn name_skeleton(name: &str) -> Vec<u8> {
+    name.bytes()
+        .filter(|&c| c != b'-' && c != b'_')
+        .map(|c| match c.to_ascii_lowercase() {
+            b'0' => b'o',
+            b'1' => b'l',
+            c => c,
+        })
+        .collect()
+}
+
+/// Levenshtein distance between two byte strings.
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let mut row: Vec<usize> = (0..b.len() + 1).collect();
+
+    for (i, &x) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, &y) in b.iter().enumerate() {
+            let substitution = diagonal + if x == y { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Orders version strings like `1.10.0` after `1.9.2`.
+///
+/// Dot-separated segments are compared numerically when both are numbers and
+/// as text otherwise, with numbers first; missing segments count as `0`. A
+/// pre-release suffix after `-` orders a version before the release.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a_release, a_pre) = split_pre_release(a);
+    let (b_release, b_pre) = split_pre_release(b);
+
+    compare_segments(a_release, b_release, true).then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => compare_segments(a, b, false),
+    })
+}
+
+fn split_pre_release(version: &str) -> (&str, Option<&str>) {
+    match version.split_once('-') {
+        Some((release, pre)) => (release, Some(pre)),
+        None => (version, None),
+    }
+}
+
+fn compare_segments(a: &str, b: &str, pad: bool) -> Ordering {
+    let mut a = a.split('.');
+    let mut b = b.split('.');
+
+    loop {
+        let ordering = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (Some(x), None) if pad => compare_segment(x, "0"),
+            (None, Some(y)) if pad => compare_segment("0", y),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (Some(x), Some(y)) => compare_segment(x, y),
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn compare_segment(a: &str, b: &str) -> Ordering {
+    let numeric = |x: &str| !x.is_empty() && x.bytes().all(|c| c.is_ascii_digit());
+
+    match (numeric(a), numeric(b)) {
+        (true, true) => {
+            let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+            a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+        }
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => a.cmp(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(node_type: NodeType, path: &str) -> ContentNode {
+        ContentNode { node_type, path: path.to_string() }
+    }
+
+    fn error_paths(nodes: Vec<ContentNode>) -> Vec<String> {
+        contents(nodes).err().unwrap().iter().map(|x| x.path().to_string()).collect()
+    }
+
+    #[test]
+    fn normalize_path_drops_empty_segments() {
+        assert_eq!(normalize_path("lib//foo/./bar.lua/").unwrap(), "lib/foo/bar.lua");
+    }
+
+    #[test]
+    fn normalize_path_rejects_escapes() {
+        match normalize_path("lib/../../etc/passwd") {
+            Err(ContentError::ParentReference { .. }) => {}
+            x => panic!("unexpected {:?}", x),
+        }
+        match normalize_path("/etc/passwd") {
+            Err(ContentError::AbsolutePath { .. }) => {}
+            x => panic!("unexpected {:?}", x),
+        }
+        match normalize_path("lib\\foo") {
+            Err(ContentError::ForbiddenCharacter { .. }) => {}
+            x => panic!("unexpected {:?}", x),
+        }
+        match normalize_path("./.") {
+            Err(ContentError::EmptyPath { .. }) => {}
+            x => panic!("unexpected {:?}", x),
+        }
+    }
+
+    #[test]
+    fn contents_adds_parents_and_sorts() {
+        let nodes = contents(vec![
+            node(NodeType::File, "lib/b/c.lua"),
+            node(NodeType::File, "./lib/a.lua"),
+        ]).ok().unwrap();
+
+        let paths: Vec<&str> = nodes.iter().map(|x| x.path.as_str()).collect();
+        assert_eq!(paths, ["lib", "lib/a.lua", "lib/b", "lib/b/c.lua"]);
+        assert!(nodes[0].node_type == NodeType::Directory);
+        assert!(nodes[1].node_type == NodeType::File);
+    }
+
+    #[test]
+    fn contents_rejects_duplicates() {
+        let paths = error_paths(vec![
+            node(NodeType::File, "lib/a.lua"),
+            node(NodeType::Directory, "lib//a.lua"),
+        ]);
+        assert_eq!(paths, ["lib//a.lua"]);
+    }
+
+    #[test]
+    fn contents_rejects_files_used_as_directories() {
+        let nodes = contents(vec![
+            node(NodeType::File, "lib"),
+            node(NodeType::File, "lib/a.lua"),
+        ]);
+
+        match nodes.err().unwrap().as_slice() {
+            [ContentError::ParentIsFile { path, parent }] => {
+                assert_eq!(path, "lib/a.lua");
+                assert_eq!(parent, "lib");
+            }
+            x => panic!("unexpected {:?}", x),
+        }
+    }
+
+    #[test]
+    fn contents_sorts_errors_by_path() {
+        let paths = error_paths(vec![
+            node(NodeType::File, "z"),
+            node(NodeType::File, "z/a"),
+            node(NodeType::File, "y"),
+            node(NodeType::File, "y/a"),
+            node(NodeType::File, "x/../a"),
+            node(NodeType::File, "b"),
+            node(NodeType::File, "b"),
+        ]);
+        assert_eq!(paths, ["b", "x/../a", "y/a", "z/a"]);
+    }
+
+    #[test]
+    fn package_name_form() {
+        let config = NamesGroup::default();
+
+        assert!(package_name("foo-bar_2", &config).is_ok());
+
+        for name in &["f", "2foo", "foo-", "foo bar", "fo\u{f6}"] {
+            assert!(package_name(name, &config).is_err(), "{} accepted", name);
+        }
+
+        match package_name(&"a".repeat(config.max_length + 1), &config) {
+            Err(ValidationError::NameLength { .. }) => {}
+            x => panic!("unexpected {:?}", x),
+        }
+        match package_name("Admin", &config) {
+            Err(ValidationError::ReservedName { .. }) => {}
+            x => panic!("unexpected {:?}", x),
+        }
+    }
+
+    #[test]
+    fn skeleton_ignores_case_separators_and_look_alikes() {
+        assert_eq!(name_skeleton("Foo_B0o-1ib"), b"fooboolib");
+    }
+
+    #[test]
+    fn edit_distance_counts_edits() {
+        assert_eq!(edit_distance(b"", b""), 0);
+        assert_eq!(edit_distance(b"abc", b""), 3);
+        assert_eq!(edit_distance(b"kitten", b"sitting"), 3);
+        assert_eq!(edit_distance(b"json", b"jsno"), 2);
+    }
+
+    #[test]
+    fn similar_names() {
+        let existing = ["json", "serialization", "Other"];
+        let similar = |name| similar_name(name, existing.iter().cloned(), 1);
+
+        assert_eq!(similar("JS0N"), Some("json"));
+        assert_eq!(similar("j_s-o_n"), Some("json"));
+        assert_eq!(similar("serialisation"), Some("serialization"));
+        // exact matches are uniqueness violations
+        assert_eq!(similar("other"), None);
+        // short names are only compared by skeleton
+        assert_eq!(similar("jso"), None);
+        assert_eq!(similar_name("JS0N", existing.iter().cloned(), 0), None);
+    }
+
+    #[test]
+    fn compare_versions_by_segment() {
+        assert_eq!(compare_versions("1.10.0", "1.9.2"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0.1", "1.0"), Ordering::Greater);
+        assert_eq!(compare_versions("2.0-beta", "2.0"), Ordering::Less);
+        assert_eq!(compare_versions("2.0-beta.2", "2.0-beta.10"), Ordering::Less);
+        assert_eq!(compare_versions("2.0-beta", "2.0-alpha"), Ordering::Greater);
+    }
+}